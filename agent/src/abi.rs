@@ -0,0 +1,519 @@
+use anyhow::{anyhow, bail, Context};
+use serde_json::{Map, Value};
+use std::{fs, path::Path};
+use web3::{
+    contract::tokens::Tokenizable,
+    ethabi::{self, Contract, Function, Int, Param, ParamType, Token, Uint},
+    types::{Address, H160, H256, U128, U256},
+};
+
+/// load a contract ABI file
+pub fn load_abi<P: AsRef<Path>>(path: P) -> anyhow::Result<Contract> {
+    let path = path.as_ref();
+    let data = fs::read(path).with_context(|| format!("failed to read abi {}", path.display()))?;
+    Contract::load(data.as_slice()).with_context(|| format!("invalid abi {}", path.display()))
+}
+
+/// load a hex bytecode file, as written by `solc --bin`
+pub fn load_code<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<u8>> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).with_context(|| format!("failed to read code {}", path.display()))?;
+    hex::decode(text.trim().trim_start_matches("0x")).with_context(|| format!("invalid code {}", path.display()))
+}
+
+/// The data of a deployment, `code` followed by the constructor arguments encoded by `encode_json_args`
+pub fn deploy_data(abi: &Contract, code: Vec<u8>, args: &Value) -> anyhow::Result<Vec<u8>> {
+    let params = abi.constructor().map(|c| c.inputs.as_slice()).unwrap_or_default();
+    let tokens = encode_json_args(params, args)?;
+    match abi.constructor() {
+        Some(constructor) => Ok(constructor.encode_input(code, &tokens)?),
+        None if tokens.is_empty() => Ok(code),
+        None => bail!("no constructor takes {} arguments", tokens.len()),
+    }
+}
+
+/// The signature of a function selector, such as `transfer(address,uint256)`
+pub fn signature(function: &Function) -> String {
+    let inputs = function.inputs.iter().map(|p| p.kind.to_string()).collect::<Vec<_>>();
+    format!("{}({})", function.name, inputs.join(","))
+}
+
+/// Find a function by name or by signature such as `transfer(address,uint256)`.
+///
+/// An overloaded function can only be selected by its signature.
+pub fn find_function<'a>(abi: &'a Contract, name: &str) -> anyhow::Result<&'a Function> {
+    let name = name.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    if name.contains('(') {
+        return abi
+            .functions()
+            .find(|f| signature(f) == name)
+            .ok_or_else(|| anyhow!("function {} not found", name));
+    }
+    let functions = abi.functions_by_name(&name)?;
+    if functions.len() > 1 {
+        let signatures = functions.iter().map(signature).collect::<Vec<_>>();
+        bail!("{} is overloaded, select one of {}", name, signatures.join(", "));
+    }
+    Ok(&functions[0])
+}
+
+// an integer from a JSON number, a decimal string or a 0x hex string, negatives in two's complement
+fn json_to_int(value: &Value, bits: usize, signed: bool) -> anyhow::Result<U256> {
+    let text = match value {
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.trim().to_owned(),
+        _ => bail!("expected an integer, got {}", value),
+    };
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.as_str()),
+    };
+    if negative && !signed {
+        bail!("{} is not an unsigned integer", text);
+    }
+    let abs = match digits.strip_prefix("0x") {
+        Some(hex) => U256::from_str_radix(hex, 16).ok(),
+        None => U256::from_dec_str(digits).ok(),
+    }
+    .ok_or_else(|| anyhow!("invalid integer {}", text))?;
+
+    let value_bits = if signed { bits - 1 } else { bits };
+    let max = if value_bits >= 256 {
+        U256::MAX
+    } else {
+        (U256::one() << value_bits) - 1
+    };
+    // the lowest signed integer is one more than the highest in magnitude
+    if abs > max && !(negative && abs == max + 1) {
+        bail!("{} is out of range of {} bits", text, bits);
+    }
+    Ok(if negative {
+        (!abs).overflowing_add(U256::one()).0
+    } else {
+        abs
+    })
+}
+
+fn json_to_bytes(value: &Value) -> anyhow::Result<Vec<u8>> {
+    let text = value
+        .as_str()
+        .ok_or_else(|| anyhow!("expected a hex string, got {}", value))?;
+    hex::decode(text.trim_start_matches("0x")).with_context(|| format!("invalid hex {}", text))
+}
+
+fn json_to_array<'a>(value: &'a Value, kind: &ParamType) -> anyhow::Result<&'a Vec<Value>> {
+    value
+        .as_array()
+        .ok_or_else(|| anyhow!("expected an array of {}, got {}", kind, value))
+}
+
+/// Token of `kind` from JSON, the reverse of `token_to_json`.
+///
+/// Integers are numbers, decimal or 0x hex strings, bytes and addresses are hex strings,
+/// arrays and tuples are JSON arrays.
+pub fn json_to_token(value: &Value, kind: &ParamType) -> anyhow::Result<Token> {
+    let token = match kind {
+        ParamType::Address => {
+            let text = value
+                .as_str()
+                .ok_or_else(|| anyhow!("expected an address, got {}", value))?;
+            Token::Address(
+                text.trim()
+                    .parse()
+                    .with_context(|| format!("invalid address {}", text))?,
+            )
+        }
+        ParamType::Bytes => Token::Bytes(json_to_bytes(value)?),
+        ParamType::FixedBytes(len) => {
+            let bytes = json_to_bytes(value)?;
+            if bytes.len() != *len {
+                bail!("expected {} bytes, got {}", len, bytes.len());
+            }
+            Token::FixedBytes(bytes)
+        }
+        ParamType::Int(bits) => Token::Int(json_to_int(value, *bits, true)?),
+        ParamType::Uint(bits) => Token::Uint(json_to_int(value, *bits, false)?),
+        ParamType::Bool => match value {
+            Value::Bool(b) => Token::Bool(*b),
+            Value::String(s) => Token::Bool(s.trim().parse().with_context(|| format!("invalid bool {}", s))?),
+            _ => bail!("expected a bool, got {}", value),
+        },
+        ParamType::String => match value {
+            Value::String(s) => Token::String(s.clone()),
+            _ => bail!("expected a string, got {}", value),
+        },
+        ParamType::Array(inner) => Token::Array(
+            json_to_array(value, kind)?
+                .iter()
+                .map(|v| json_to_token(v, inner))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        ParamType::FixedArray(inner, len) => {
+            let values = json_to_array(value, kind)?;
+            if values.len() != *len {
+                bail!("expected {} items of {}, got {}", len, inner, values.len());
+            }
+            Token::FixedArray(
+                values
+                    .iter()
+                    .map(|v| json_to_token(v, inner))
+                    .collect::<anyhow::Result<_>>()?,
+            )
+        }
+        ParamType::Tuple(kinds) => {
+            let values = json_to_array(value, kind)?;
+            if values.len() != kinds.len() {
+                bail!("expected {} fields of {}, got {}", kinds.len(), kind, values.len());
+            }
+            Token::Tuple(
+                values
+                    .iter()
+                    .zip(kinds)
+                    .map(|(v, k)| json_to_token(v, k))
+                    .collect::<anyhow::Result<_>>()?,
+            )
+        }
+    };
+    Ok(token)
+}
+
+/// Encode JSON arguments by the input types of a function or constructor
+pub fn encode_args(params: &[Param], args: &[Value]) -> anyhow::Result<Vec<Token>> {
+    if params.len() != args.len() {
+        bail!("expected {} arguments, got {}", params.len(), args.len());
+    }
+    params
+        .iter()
+        .zip(args)
+        .map(|(p, v)| json_to_token(v, &p.kind).with_context(|| format!("invalid argument {} {}", p.kind, p.name)))
+        .collect()
+}
+
+/// Encode the arguments of a JSON array by the ABI types, or the types of a CSV string guessed by `parse_args_csv`
+pub fn encode_json_args(params: &[Param], args: &Value) -> anyhow::Result<Vec<Token>> {
+    match args {
+        Value::Array(args) => encode_args(params, args),
+        Value::String(args) => parse_args_csv(args),
+        Value::Null => encode_args(params, &[]),
+        _ => bail!("args should be a JSON array, got {}", args),
+    }
+}
+
+/// Tokens of comma-separated values, the type of each value is guessed by its format
+pub fn parse_args_csv(args: &str) -> anyhow::Result<Vec<Token>> {
+    let mut res: Vec<Token> = Vec::new();
+
+    let args_str = args.to_string();
+    let mut csv_reader1 = csv::Reader::from_reader(args_str.as_bytes());
+
+    if let Ok(args) = csv_reader1.headers() {
+        for arg in args {
+            if arg.is_empty() {
+                bail!("arg format error!!!");
+            } else if let Ok(arg_bool) = arg.parse::<bool>() {
+                res.push(arg_bool.into_token());
+            } else if let Ok(arg_int) = arg.parse::<Int>() {
+                res.push(arg_int.into_token());
+            } else if let Ok(arg_uint) = arg.parse::<Uint>() {
+                res.push(arg_uint.into_token());
+            } else if let Ok(arg_address) = arg.parse::<Address>() {
+                res.push(arg_address.into_token());
+            } else if let Ok(arg_h160) = arg.parse::<H160>() {
+                res.push(arg_h160.into_token());
+            } else if let Ok(arg_h256) = arg.parse::<H256>() {
+                res.push(arg_h256.into_token());
+            } else if let Ok(arg_u128) = arg.parse::<U128>() {
+                res.push(arg_u128.into_token());
+            } else if let Ok(arg_u256) = arg.parse::<U256>() {
+                res.push(arg_u256.into_token());
+            } else {
+                let arg_string = arg.to_string();
+                res.push(arg_string.into_token());
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+// a two's complement 256-bit integer in decimal
+fn int_to_string(v: &U256) -> String {
+    if v.bit(255) {
+        format!("-{}", (!*v).overflowing_add(U256::one()).0)
+    } else {
+        v.to_string()
+    }
+}
+
+/// JSON of a token, integers are decimal strings so they keep all 256 bits
+pub fn token_to_json(token: &Token) -> Value {
+    match token {
+        Token::Address(a) => Value::String(format!("{a:?}")),
+        Token::FixedBytes(b) | Token::Bytes(b) => Value::String(format!("0x{}", hex::encode(b))),
+        Token::Int(v) => Value::String(int_to_string(v)),
+        Token::Uint(v) => Value::String(v.to_string()),
+        Token::Bool(b) => Value::Bool(*b),
+        Token::String(s) => Value::String(s.clone()),
+        Token::FixedArray(tokens) | Token::Array(tokens) | Token::Tuple(tokens) => {
+            Value::Array(tokens.iter().map(token_to_json).collect())
+        }
+    }
+}
+
+/// JSON of the outputs of a function.
+///
+/// A single output is its value, named outputs are an object, others are an array.
+pub fn outputs_to_json(outputs: &[Param], tokens: &[Token]) -> Value {
+    if tokens.len() == 1 && outputs.len() <= 1 {
+        return token_to_json(&tokens[0]);
+    }
+    let named = outputs.len() == tokens.len()
+        && outputs.iter().all(|p| !p.name.is_empty())
+        && outputs
+            .iter()
+            .enumerate()
+            .all(|(i, p)| outputs[..i].iter().all(|q| q.name != p.name));
+    if named {
+        let map = outputs
+            .iter()
+            .zip(tokens)
+            .map(|(p, t)| (p.name.clone(), token_to_json(t)))
+            .collect::<Map<_, _>>();
+        Value::Object(map)
+    } else {
+        Value::Array(tokens.iter().map(token_to_json).collect())
+    }
+}
+
+// the selectors of `Error(string)` and `Panic(uint256)` reverts
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+// the meaning of a solidity panic code
+fn panic_reason(code: &U256) -> &'static str {
+    match code.low_u64() {
+        0x00 => "generic panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic overflow",
+        0x12 => "division by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array",
+        0x31 => "pop on an empty array",
+        0x32 => "index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "uninitialized function",
+        _ => "unknown panic",
+    }
+}
+
+/// Decode the return data of a reverted call.
+///
+/// `Error(string)` and `Panic(uint256)` are built in, custom errors are looked up in `abi`.
+pub fn decode_revert(data: &[u8], abi: Option<&Contract>) -> String {
+    if data.is_empty() {
+        return "reverted without a reason".to_string();
+    }
+    if data.len() < 4 {
+        return format!("invalid revert data 0x{}", hex::encode(data));
+    }
+    let (selector, args) = data.split_at(4);
+    if selector == ERROR_SELECTOR {
+        if let Ok(Some(Token::String(reason))) =
+            ethabi::decode(&[ParamType::String], args).map(|t| t.into_iter().next())
+        {
+            return format!("Error({reason:?})");
+        }
+    } else if selector == PANIC_SELECTOR {
+        if let Ok(Some(Token::Uint(code))) = ethabi::decode(&[ParamType::Uint(256)], args).map(|t| t.into_iter().next())
+        {
+            return format!("Panic(0x{code:x}: {})", panic_reason(&code));
+        }
+    } else if let Some(abi) = abi {
+        for error in abi.errors().filter(|e| e.signature().as_bytes()[..4] == *selector) {
+            if let Ok(tokens) = error.decode(args) {
+                let values = tokens.iter().map(|t| token_to_json(t).to_string()).collect::<Vec<_>>();
+                return format!("{}({})", error.name, values.join(", "));
+            }
+        }
+    }
+    format!("unknown revert 0x{}", hex::encode(data))
+}
+
+/// The revert data in the `data` of a JSON-RPC error.
+///
+/// Nodes give a hex string, a text such as `Reverted 0x...`, or an object with a `data` field.
+pub fn revert_data(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::String(text) => {
+            let hex = &text[text.find("0x")? + 2..];
+            hex::decode(hex.trim()).ok()
+        }
+        Value::Object(map) => map.get("data").and_then(revert_data),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn contract() -> Contract {
+        let abi = json!([
+            {
+                "type": "constructor",
+                "inputs": [{ "name": "supply", "type": "uint256" }],
+                "stateMutability": "nonpayable"
+            },
+            {
+                "type": "function",
+                "name": "transfer",
+                "inputs": [{ "name": "to", "type": "address" }, { "name": "amount", "type": "uint256" }],
+                "outputs": [],
+                "stateMutability": "nonpayable"
+            },
+            {
+                "type": "function",
+                "name": "transfer",
+                "inputs": [{ "name": "to", "type": "address" }],
+                "outputs": [],
+                "stateMutability": "nonpayable"
+            },
+            {
+                "type": "function",
+                "name": "total",
+                "inputs": [],
+                "outputs": [{ "name": "", "type": "uint256" }],
+                "stateMutability": "view"
+            }
+        ]);
+        Contract::load(abi.to_string().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn integers() {
+        let uint = |v: Value, bits| json_to_token(&v, &ParamType::Uint(bits));
+        let int = |v: Value, bits| json_to_token(&v, &ParamType::Int(bits));
+        assert_eq!(uint(json!(42), 8).unwrap(), Token::Uint(42.into()));
+        assert_eq!(uint(json!("42"), 8).unwrap(), Token::Uint(42.into()));
+        assert_eq!(uint(json!("0xff"), 8).unwrap(), Token::Uint(255.into()));
+        assert_eq!(uint(json!(" 7 "), 256).unwrap(), Token::Uint(7.into()));
+        assert!(uint(json!(256), 8).is_err());
+        assert!(uint(json!(-1), 8).is_err());
+        assert!(uint(json!("x"), 8).is_err());
+        assert!(uint(json!(true), 8).is_err());
+
+        assert_eq!(int(json!(-1), 8).unwrap(), Token::Int(U256::MAX));
+        assert_eq!(int(json!(127), 8).unwrap(), Token::Int(127.into()));
+        assert_eq!(int(json!(-128), 8).unwrap(), Token::Int(U256::MAX - 127));
+        assert!(int(json!(128), 8).is_err());
+        assert!(int(json!(-129), 8).is_err());
+        assert_eq!(int(json!(-1), 256).unwrap(), Token::Int(U256::MAX));
+    }
+
+    #[test]
+    fn other_tokens() {
+        let address = "0x00000000000000000000000000000000000000ff";
+        assert_eq!(
+            json_to_token(&json!(address), &ParamType::Address).unwrap(),
+            Token::Address(address.parse().unwrap())
+        );
+        assert!(json_to_token(&json!("0xff"), &ParamType::Address).is_err());
+
+        assert_eq!(
+            json_to_token(&json!("0x0102"), &ParamType::Bytes).unwrap(),
+            Token::Bytes(vec![1, 2])
+        );
+        assert_eq!(
+            json_to_token(&json!("0102"), &ParamType::FixedBytes(2)).unwrap(),
+            Token::FixedBytes(vec![1, 2])
+        );
+        assert!(json_to_token(&json!("0x01"), &ParamType::FixedBytes(2)).is_err());
+
+        assert_eq!(
+            json_to_token(&json!(true), &ParamType::Bool).unwrap(),
+            Token::Bool(true)
+        );
+        assert_eq!(
+            json_to_token(&json!("false"), &ParamType::Bool).unwrap(),
+            Token::Bool(false)
+        );
+        assert_eq!(
+            json_to_token(&json!("hi"), &ParamType::String).unwrap(),
+            Token::String("hi".to_owned())
+        );
+        assert!(json_to_token(&json!(1), &ParamType::String).is_err());
+
+        let array = ParamType::Array(Box::new(ParamType::Uint(256)));
+        assert_eq!(
+            json_to_token(&json!([1, "2"]), &array).unwrap(),
+            Token::Array(vec![Token::Uint(1.into()), Token::Uint(2.into())])
+        );
+        let fixed = ParamType::FixedArray(Box::new(ParamType::Bool), 2);
+        assert!(json_to_token(&json!([true]), &fixed).is_err());
+        let tuple = ParamType::Tuple(vec![ParamType::Bool, ParamType::String]);
+        assert_eq!(
+            json_to_token(&json!([true, "a"]), &tuple).unwrap(),
+            Token::Tuple(vec![Token::Bool(true), Token::String("a".to_owned())])
+        );
+        assert!(json_to_token(&json!([true]), &tuple).is_err());
+    }
+
+    #[test]
+    fn arguments() {
+        let abi = contract();
+        let function = find_function(&abi, "transfer(address,uint256)").unwrap();
+        let to = "0x00000000000000000000000000000000000000ff";
+        assert_eq!(
+            encode_args(&function.inputs, &[json!(to), json!(1)]).unwrap(),
+            vec![Token::Address(to.parse().unwrap()), Token::Uint(1.into())]
+        );
+        assert!(encode_args(&function.inputs, &[json!(to)]).is_err());
+        let err = encode_args(&function.inputs, &[json!(to), json!(-1)]).unwrap_err();
+        assert!(format!("{err:#}").contains("invalid argument uint256 amount"));
+
+        assert_eq!(encode_json_args(&[], &Value::Null).unwrap(), vec![]);
+        assert!(encode_json_args(&function.inputs, &Value::Null).is_err());
+        assert!(encode_json_args(&function.inputs, &json!({ "to": to })).is_err());
+    }
+
+    #[test]
+    fn overloads() {
+        let abi = contract();
+        assert_eq!(find_function(&abi, "total").unwrap().name, "total");
+        assert_eq!(find_function(&abi, "total()").unwrap().name, "total");
+        assert_eq!(
+            signature(find_function(&abi, "transfer(address)").unwrap()),
+            "transfer(address)"
+        );
+        assert_eq!(
+            signature(find_function(&abi, "transfer( address, uint256 )").unwrap()),
+            "transfer(address,uint256)"
+        );
+        let err = find_function(&abi, "transfer").unwrap_err().to_string();
+        assert!(err.contains("transfer(address,uint256)") && err.contains("transfer(address)"));
+        assert!(find_function(&abi, "transfer(uint256)").is_err());
+        assert!(find_function(&abi, "mint").is_err());
+    }
+
+    #[test]
+    fn csv_arguments() {
+        let tokens = parse_args_csv("true,hello").unwrap();
+        assert_eq!(tokens, vec![Token::Bool(true), Token::String("hello".to_owned())]);
+        assert!(matches!(parse_args_csv("10").unwrap().as_slice(), [Token::Uint(_)]));
+        assert!(parse_args_csv("true,,1").is_err());
+    }
+
+    #[test]
+    fn deployment() {
+        let abi = contract();
+        let data = deploy_data(&abi, vec![0xfe], &json!([1])).unwrap();
+        assert_eq!(data.len(), 33);
+        assert_eq!((data[0], data[32]), (0xfe, 1));
+        assert!(deploy_data(&abi, vec![0xfe], &json!([])).is_err());
+
+        let abi = Contract::load(b"[]".as_slice()).unwrap();
+        assert_eq!(deploy_data(&abi, vec![0xfe], &Value::Null).unwrap(), vec![0xfe]);
+        assert!(deploy_data(&abi, vec![0xfe], &json!([1])).is_err());
+    }
+}
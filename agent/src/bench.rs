@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use web3::ethabi::Contract;
+
+/// Benchmark contracts shipped with the binary, so no toolchain is needed to deploy them.
+///
+/// They're assembled by `scripts/bench_contracts.py` into `agent/contracts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BenchContract {
+    /// an ERC20 token, the deployer holds 1e9 tokens and anyone can `mint`
+    Erc20,
+    /// `set`/`get` of keys, `write(count)` appends `count` new keys
+    Storage,
+    /// `run(rounds)` hashes `rounds` times
+    Compute,
+    /// `emitEvents(count)` emits `count` `Ping` events
+    Emitter,
+}
+
+impl BenchContract {
+    pub const ALL: [BenchContract; 4] = [Self::Erc20, Self::Storage, Self::Compute, Self::Emitter];
+
+    pub fn abi_json(&self) -> &'static str {
+        match self {
+            Self::Erc20 => include_str!("../contracts/erc20.abi"),
+            Self::Storage => include_str!("../contracts/storage.abi"),
+            Self::Compute => include_str!("../contracts/compute.abi"),
+            Self::Emitter => include_str!("../contracts/emitter.abi"),
+        }
+    }
+
+    /// the deployment code in hex, without constructor arguments
+    pub fn code_hex(&self) -> &'static str {
+        match self {
+            Self::Erc20 => include_str!("../contracts/erc20.bin"),
+            Self::Storage => include_str!("../contracts/storage.bin"),
+            Self::Compute => include_str!("../contracts/compute.bin"),
+            Self::Emitter => include_str!("../contracts/emitter.bin"),
+        }
+        .trim()
+    }
+
+    pub fn abi(&self) -> Contract {
+        Contract::load(self.abi_json().as_bytes()).expect("invalid abi of a builtin contract")
+    }
+
+    pub fn code(&self) -> Vec<u8> {
+        hex::decode(self.code_hex()).expect("invalid code of a builtin contract")
+    }
+}
+
+impl std::fmt::Display for BenchContract {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Erc20 => write!(f, "erc20"),
+            Self::Storage => write!(f, "storage"),
+            Self::Compute => write!(f, "compute"),
+            Self::Emitter => write!(f, "emitter"),
+        }
+    }
+}
+
+impl std::str::FromStr for BenchContract {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|c| c.to_string() == s.to_lowercase().trim())
+            .ok_or_else(|| {
+                format!("invalid builtin contract {s}, 'erc20', 'storage', 'compute', 'emitter' are supported")
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(code: &[u8], pattern: &[u8]) -> bool {
+        code.windows(pattern.len()).any(|w| w == pattern)
+    }
+
+    // the dispatcher compares selectors pushed by PUSH4
+    #[test]
+    fn functions_are_dispatched() {
+        for contract in BenchContract::ALL {
+            let code = contract.code();
+            for function in contract.abi().functions() {
+                let mut pattern = vec![0x63];
+                pattern.extend(function.short_signature());
+                assert!(
+                    contains(&code, &pattern),
+                    "{contract}: no selector of {}",
+                    function.signature()
+                );
+            }
+        }
+    }
+
+    // the topic of an event is pushed by PUSH32 before LOGn
+    #[test]
+    fn events_are_emitted() {
+        for contract in BenchContract::ALL {
+            let code = contract.code();
+            for event in contract.abi().events() {
+                let mut pattern = vec![0x7f];
+                pattern.extend(event.signature().as_bytes());
+                assert!(contains(&code, &pattern), "{contract}: no topic of {}", event.name);
+            }
+        }
+    }
+
+    #[test]
+    fn names() {
+        for contract in BenchContract::ALL {
+            assert_eq!(contract.to_string().parse::<BenchContract>(), Ok(contract));
+        }
+        assert!("erc721".parse::<BenchContract>().is_err());
+    }
+}
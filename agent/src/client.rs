@@ -0,0 +1,652 @@
+use crate::{
+    abi,
+    endpoint::{EndpointPolicy, EndpointPool, Transport},
+    error::{Error, Result},
+    metrics::{now_millis, TxRecord},
+    presign::RawTx,
+    rate::{RateController, DEFAULT_MAX_RATE, DEFAULT_MIN_RATE},
+    tx::TxFee,
+    utils::{extract_keypair_from_file, real_network},
+    TestClientOpts, BLOCK_TIME, FRC20_ADDRESS, WEB3_SRV,
+};
+use std::{error::Error as StdError, future::Future, sync::Arc, time::Duration};
+use tracing::{error, info, warn};
+use web3::{
+    self,
+    api::{Accounts, Eth},
+    signing::{Key, SecretKeyRef},
+    types::{
+        Address, Block, BlockId, BlockNumber, Bytes, CallRequest, FilterBuilder, Log, SignedTransaction, Transaction,
+        TransactionId, TransactionParameters, TransactionReceipt, H160, H256, U256, U64,
+    },
+};
+
+// the interval of polling the receipt of a distribution tx
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The result of a call simulated by `eth_call`
+#[derive(Debug, Clone)]
+pub enum CallOutcome {
+    Success(Bytes),
+    /// the revert data, empty if the node gives none, and the error message of the node
+    Reverted {
+        data: Vec<u8>,
+        message: String,
+    },
+}
+
+/// An async-first client, all requests are driven by the caller's tokio runtime.
+///
+/// It's cheap to clone, clones share the same endpoints and http connection pools.
+#[derive(Debug, Clone)]
+pub struct AsyncTestClient {
+    pub pool: Arc<EndpointPool>,
+    /// the root key and address, if a secret file is given
+    pub root: Option<(secp256k1::SecretKey, Address)>,
+    /// the send rate shared by all senders
+    pub rate: Arc<RateController>,
+}
+
+impl AsyncTestClient {
+    pub fn setup(url: Option<String>, timeout: Option<u64>) -> Result<Self> {
+        let opts = TestClientOpts {
+            endpoint_url: url,
+            timeout,
+            ..Default::default()
+        };
+
+        Self::setup_with_opts(opts)
+    }
+
+    /// connect to the endpoints of `opts`, fails if any endpoint can't be connected
+    pub fn setup_with_opts(opts: TestClientOpts) -> Result<Self> {
+        let TestClientOpts {
+            endpoint_url: url,
+            secret_file,
+            timeout,
+            policy,
+            max_rate,
+        } = opts;
+
+        // comma seperated network endpoints
+        let mut urls = real_network(url.as_deref().unwrap_or(WEB3_SRV))
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        if urls.is_empty() {
+            urls.push(WEB3_SRV.to_owned());
+        }
+        let pool = Arc::new(EndpointPool::new(&urls, timeout, policy)?);
+        let root = secret_file.and_then(|secret| match extract_keypair_from_file(&secret) {
+            Ok(root) => Some(root),
+            Err(e) => {
+                error!("failed to load the root key {}: {}", secret, e);
+                None
+            }
+        });
+
+        let rate = Arc::new(RateController::new(
+            DEFAULT_MIN_RATE,
+            max_rate.unwrap_or(DEFAULT_MAX_RATE),
+        ));
+
+        Ok(Self { pool, root, rate })
+    }
+
+    /// the root key, it's loaded from `TestClientOpts::secret_file`
+    pub fn root(&self) -> Result<(secp256k1::SecretKey, Address)> {
+        self.root
+            .ok_or_else(|| Error::Other("no root key, a secret file is required".to_string()))
+    }
+
+    /// web3 instance of an endpoint selected by the policy
+    pub fn web3(&self) -> web3::Web3<Transport> {
+        self.pool.get(self.pool.select(None)).web3.clone()
+    }
+
+    pub fn eth(&self) -> Eth<Transport> {
+        self.web3().eth()
+    }
+
+    pub fn accounts(&self) -> Accounts<Transport> {
+        self.web3().accounts()
+    }
+
+    pub fn policy(&self) -> EndpointPolicy {
+        self.pool.policy()
+    }
+
+    /// Run a request on an endpoint selected by the policy, `key` is the source account if any.
+    ///
+    /// With `EndpointPolicy::Failover`, the request is retried on the next endpoint if it can't be sent.
+    pub async fn request<F, Fut, T>(&self, key: Option<Address>, f: F) -> web3::Result<T>
+    where
+        F: Fn(web3::Web3<Transport>) -> Fut,
+        Fut: Future<Output = web3::Result<T>>,
+    {
+        let mut idx = self.pool.select(key.as_ref());
+        let mut tries = 1;
+        loop {
+            let now = std::time::Instant::now();
+            let res = f(self.pool.get(idx).web3.clone()).await;
+            self.pool.record(idx, res.is_ok(), now.elapsed());
+            match res {
+                Err(e)
+                    if self.pool.policy() == EndpointPolicy::Failover
+                        && tries < self.pool.len()
+                        && matches!(self.decode_error(&e), Error::SendErr | Error::Timeout(_)) =>
+                {
+                    idx = self.pool.failover(idx);
+                    tries += 1;
+                }
+                res => break res,
+            }
+        }
+    }
+
+    pub async fn chain_id(&self) -> Option<U256> {
+        self.request(None, |web3| async move { web3.eth().chain_id().await })
+            .await
+            .ok()
+    }
+
+    pub async fn block_number(&self) -> Option<U64> {
+        self.request(None, |web3| async move { web3.eth().block_number().await })
+            .await
+            .ok()
+    }
+
+    pub async fn current_block(&self) -> Option<Block<H256>> {
+        self.request(None, |web3| async move {
+            web3.eth().block(BlockId::Number(BlockNumber::Latest)).await
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    pub async fn block_with_tx_hashes(&self, id: BlockId) -> Option<Block<H256>> {
+        self.block_with_tx_hashes_inner(id, None, None).await
+    }
+
+    pub async fn block_with_tx_hashes_inner(
+        &self,
+        id: BlockId,
+        interval: Option<u64>,
+        times: Option<u64>,
+    ) -> Option<Block<H256>> {
+        let interval = interval.unwrap_or(1);
+        let mut retries = 1u64;
+        loop {
+            if let Ok(Some(block)) = self
+                .request(None, |web3| async move { web3.eth().block(id).await })
+                .await
+            {
+                break Some(block);
+            }
+            if times == Some(retries) || times == Some(0u64) {
+                break None;
+            }
+            retries += 1;
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+        }
+    }
+
+    pub async fn nonce(&self, from: Address, block: Option<BlockNumber>) -> Option<U256> {
+        self.request(Some(from), |web3| async move {
+            web3.eth().transaction_count(from, block).await
+        })
+        .await
+        .ok()
+    }
+
+    pub async fn pending_nonce(&self, from: Address) -> Option<U256> {
+        self.pending_nonce_inner(from, Some(3), None).await
+    }
+
+    pub async fn pending_nonce_inner(&self, from: Address, interval: Option<u64>, times: Option<u64>) -> Option<U256> {
+        let interval = interval.unwrap_or(5);
+        let mut tries = 1u64;
+        loop {
+            match self
+                .request(Some(from), |web3| async move {
+                    web3.eth().transaction_count(from, Some(BlockNumber::Pending)).await
+                })
+                .await
+            {
+                Ok(nonce) => break Some(nonce),
+                Err(e) => error!("failed to get nonce, tries {}, {:?}", tries, e),
+            }
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            if times == Some(tries) || times == Some(0u64) {
+                break None;
+            }
+            tries += 1;
+        }
+    }
+
+    pub async fn gas_price(&self) -> Option<U256> {
+        self.request(None, |web3| async move { web3.eth().gas_price().await })
+            .await
+            .ok()
+    }
+
+    pub async fn block_with_txs(&self, id: BlockId) -> Option<Block<Transaction>> {
+        self.request(None, |web3| async move { web3.eth().block_with_txs(id).await })
+            .await
+            .unwrap_or_default()
+    }
+
+    /// the fee of the next tx, the gas price is taken from the oracle of `fee` if any
+    pub async fn resolve_fee(&self, fee: &TxFee) -> Result<TxFee> {
+        let mut fee = fee.clone();
+        if let Some(oracle) = fee.oracle.as_ref() {
+            fee.gas_price = Some(oracle.gas_price(self).await?);
+        }
+        Ok(fee)
+    }
+
+    pub async fn frc20_code(&self) -> Option<Bytes> {
+        self.request(None, |web3| async move {
+            web3.eth().code(H160::from_low_u64_be(FRC20_ADDRESS), None).await
+        })
+        .await
+        .ok()
+    }
+
+    pub async fn transaction(&self, id: TransactionId) -> Option<Transaction> {
+        self.request(None, |web3| {
+            let id = id.clone();
+            async move { web3.eth().transaction(id).await }
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    pub async fn transaction_receipt(&self, hash: H256) -> Option<TransactionReceipt> {
+        self.request(None, |web3| async move { web3.eth().transaction_receipt(hash).await })
+            .await
+            .unwrap_or_default()
+    }
+
+    /// the logs of `address` from block `from` to `to`, both inclusive
+    pub async fn logs(&self, address: Address, from: u64, to: u64) -> Result<Vec<Log>> {
+        let filter = FilterBuilder::default()
+            .address(vec![address])
+            .from_block(BlockNumber::Number(from.into()))
+            .to_block(BlockNumber::Number(to.into()))
+            .build();
+        self.request(None, |web3| {
+            let filter = filter.clone();
+            async move { web3.eth().logs(filter).await }
+        })
+        .await
+        .map_err(|e| self.decode_error(&e))
+    }
+
+    pub async fn node_accounts(&self) -> Vec<Address> {
+        self.request(None, |web3| async move { web3.eth().accounts().await })
+            .await
+            .unwrap_or_default()
+    }
+
+    pub async fn balance(&self, address: Address, number: Option<BlockNumber>) -> U256 {
+        self.request(Some(address), |web3| async move {
+            web3.eth().balance(address, number).await
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    pub async fn wait_for_tx_receipt(
+        &self,
+        hash: H256,
+        interval: Duration,
+        times: u64,
+    ) -> (u64, Option<TransactionReceipt>) {
+        // at least one attempt
+        let times = times.max(1);
+        for attempt in 1..=times {
+            if let Some(receipt) = self.transaction_receipt(hash).await {
+                return (attempt, Some(receipt));
+            }
+            if attempt < times {
+                tokio::time::sleep(interval).await;
+            }
+        }
+        (0, None)
+    }
+
+    pub fn parse_error(&self, err: Option<&(dyn StdError + 'static)>) -> Error {
+        match err {
+            Some(e) => match e.downcast_ref::<web3::Error>() {
+                Some(e) => self.decode_error(e),
+                None => Error::from_text(e.to_string()),
+            },
+            None => Error::Other("empty error".to_string()),
+        }
+    }
+
+    /// classify an error of a request by its JSON-RPC code, message and data
+    pub fn decode_error(&self, err: &web3::Error) -> Error {
+        Error::from(err.clone())
+    }
+
+    pub async fn distribution_simple(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        chain_id: Option<u64>,
+        fee: &TxFee,
+        nonce: Option<U256>,
+    ) -> Result<H256> {
+        let (account, amount) = target;
+        let mut tx_object = TransactionParameters {
+            to: Some(*account),
+            value: *amount,
+            chain_id,
+            nonce,
+            ..Default::default()
+        };
+        self.resolve_fee(fee).await?.apply(&mut tx_object);
+        // Sign the txs (can be done offline)
+        let signed = self
+            .sign_transaction(tx_object, source)
+            .await
+            .map_err(|e| self.decode_error(&e))?;
+        self.send_signed(Some(SecretKeyRef::new(source).address()), signed.raw_transaction)
+            .await
+    }
+
+    /// Send a contract call to `target.0` with `target.1` wei, `data` is the encoded function call
+    #[allow(clippy::too_many_arguments)]
+    pub async fn contract_call_simple(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        data: &Bytes,
+        gas: U256,
+        chain_id: Option<u64>,
+        fee: &TxFee,
+        nonce: Option<U256>,
+    ) -> Result<H256> {
+        let (contract, value) = target;
+        let mut tx_object = TransactionParameters {
+            to: Some(*contract),
+            value: *value,
+            gas,
+            data: data.clone(),
+            chain_id,
+            nonce,
+            ..Default::default()
+        };
+        self.resolve_fee(fee).await?.apply(&mut tx_object);
+        let signed = self
+            .sign_transaction(tx_object, source)
+            .await
+            .map_err(|e| self.decode_error(&e))?;
+        self.send_signed(Some(SecretKeyRef::new(source).address()), signed.raw_transaction)
+            .await
+    }
+
+    pub async fn estimate_gas(&self, req: CallRequest) -> Result<U256> {
+        self.request(req.from, |web3| {
+            let req = req.clone();
+            async move { web3.eth().estimate_gas(req, None).await }
+        })
+        .await
+        .map_err(|e| self.decode_error(&e))
+    }
+
+    /// Simulate a call by `eth_call` at `block`, the latest by default
+    pub async fn simulate_call(&self, req: CallRequest, block: Option<BlockId>) -> Result<CallOutcome> {
+        let res = self
+            .request(req.from, |web3| {
+                let req = req.clone();
+                async move { web3.eth().call(req, block).await }
+            })
+            .await;
+        match res {
+            Ok(data) => Ok(CallOutcome::Success(data)),
+            Err(web3::Error::Rpc(e)) => {
+                let data = e.data.as_ref().and_then(abi::revert_data);
+                if data.is_some() || e.message.to_lowercase().contains("revert") {
+                    Ok(CallOutcome::Reverted {
+                        data: data.unwrap_or_default(),
+                        message: e.message,
+                    })
+                } else {
+                    Err(Error::from(web3::Error::Rpc(e)))
+                }
+            }
+            Err(e) => Err(self.decode_error(&e)),
+        }
+    }
+
+    /// Replay a mined transaction by `eth_call` on the state before its block, to get its revert data.
+    ///
+    /// Transactions before it in the same block are not replayed, so the result may differ.
+    pub async fn replay_transaction(&self, hash: H256) -> Result<CallOutcome> {
+        let tx = self
+            .transaction(TransactionId::Hash(hash))
+            .await
+            .ok_or_else(|| Error::Other(format!("transaction {hash:?} not found")))?;
+        let block = tx
+            .block_number
+            .map(|n| BlockId::Number(BlockNumber::Number(n.saturating_sub(U64::one()))));
+        let req = CallRequest {
+            from: tx.from,
+            to: tx.to,
+            gas: Some(tx.gas),
+            value: Some(tx.value),
+            data: Some(tx.input),
+            ..Default::default()
+        };
+        self.simulate_call(req, block).await
+    }
+
+    /// Deploy a contract and wait for its receipt, `data` is the code and the encoded constructor arguments.
+    ///
+    /// The gas limit is estimated with a 20% margin if not given.
+    pub async fn deploy_contract(
+        &self,
+        source: &secp256k1::SecretKey,
+        data: Bytes,
+        gas: Option<U256>,
+        fee: &TxFee,
+    ) -> Result<(Address, TransactionReceipt)> {
+        let from = SecretKeyRef::new(source).address();
+        let gas = match gas {
+            Some(gas) => gas,
+            None => {
+                let req = CallRequest {
+                    from: Some(from),
+                    data: Some(data.clone()),
+                    ..Default::default()
+                };
+                self.estimate_gas(req).await? * 6 / 5
+            }
+        };
+        let mut tx_object = TransactionParameters {
+            to: None,
+            gas,
+            data,
+            ..Default::default()
+        };
+        self.resolve_fee(fee).await?.apply(&mut tx_object);
+        let signed = self
+            .sign_transaction(tx_object, source)
+            .await
+            .map_err(|e| self.decode_error(&e))?;
+        let hash = self.send_signed(Some(from), signed.raw_transaction).await?;
+        info!("deploying contract by {:?}, tx {:?}", from, hash);
+
+        // wait for at most 10 blocks
+        let (_, receipt) = self
+            .wait_for_tx_receipt(hash, Duration::from_secs(1), BLOCK_TIME * 10)
+            .await;
+        match receipt {
+            Some(receipt) if receipt.status == Some(U64::one()) => match receipt.contract_address {
+                Some(address) => Ok((address, receipt)),
+                None => Err(Error::Other(format!("no contract address in receipt of {hash:?}"))),
+            },
+            Some(_) => Err(Error::Other(format!("deployment {hash:?} failed"))),
+            None => Err(Error::Timeout(format!("no receipt of deployment {hash:?}"))),
+        }
+    }
+
+    /// Sign a transfer without any request, the fee must carry a gas price.
+    pub async fn sign_transfer_offline(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        chain_id: u64,
+        fee: &TxFee,
+        nonce: U256,
+    ) -> Result<RawTx> {
+        if fee.gas_price.is_none() && fee.max_fee_per_gas.is_none() {
+            return Err(Error::Other("a gas price is required to sign offline".to_string()));
+        }
+        let (account, amount) = target;
+        let mut tx_object = TransactionParameters {
+            to: Some(*account),
+            value: *amount,
+            chain_id: Some(chain_id),
+            nonce: Some(nonce),
+            ..Default::default()
+        };
+        fee.apply(&mut tx_object);
+        // nonce, gas price and chain id are all known, no request is sent
+        let signed = self
+            .pool
+            .get(0)
+            .web3
+            .accounts()
+            .sign_transaction(tx_object, source)
+            .await
+            .map_err(|e| self.decode_error(&e))?;
+        Ok(RawTx {
+            source: SecretKeyRef::new(source).address(),
+            target: *account,
+            nonce,
+            hash: signed.transaction_hash,
+            raw: signed.raw_transaction,
+        })
+    }
+
+    /// Send a signed transaction at the shared send rate, the rate is cut if the mempool is full
+    pub async fn send_signed(&self, from: Option<Address>, raw: Bytes) -> Result<H256> {
+        self.rate.acquire().await;
+        match self.send_raw_transaction(from, raw).await {
+            Ok(hash) => {
+                self.rate.on_accepted();
+                Ok(hash)
+            }
+            Err(e) => {
+                let err = self.decode_error(&e);
+                if let Error::MempoolFull(_) = err {
+                    self.rate.on_rejected();
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// sign a transaction, missing fields are filled by an endpoint
+    pub async fn sign_transaction(
+        &self,
+        tx: TransactionParameters,
+        key: &secp256k1::SecretKey,
+    ) -> web3::Result<SignedTransaction> {
+        let from = SecretKeyRef::new(key).address();
+        self.request(Some(from), |web3| {
+            let tx = tx.clone();
+            async move { web3.accounts().sign_transaction(tx, key).await }
+        })
+        .await
+    }
+
+    /// send a signed transaction, `from` is the sender if known
+    pub async fn send_raw_transaction(&self, from: Option<Address>, raw: Bytes) -> web3::Result<H256> {
+        self.request(from, |web3| {
+            let raw = raw.clone();
+            async move { web3.eth().send_raw_transaction(raw).await }
+        })
+        .await
+    }
+
+    /// Send txs to `targets` one by one, waiting for the receipt of each tx
+    pub async fn distribute(
+        &self,
+        source: &secp256k1::SecretKey,
+        targets: &[(Address, U256)],
+        delay_in_seconds: u64,
+        fee: &TxFee,
+    ) -> Result<Vec<TxRecord>> {
+        let from = SecretKeyRef::new(source).address();
+        let mut records = Vec::with_capacity(targets.len());
+        for target in targets {
+            let (account, amount) = target;
+            let mut record = TxRecord {
+                source: from,
+                target: *account,
+                ..Default::default()
+            };
+            if let Err(e) = self.distribute_one(source, target, fee, &mut record).await {
+                error!("{:?}: {:?}", account, e);
+                record.error = Some(e.to_string());
+                records.push(record);
+                continue;
+            }
+            match record.status {
+                Some(status) => info!("{account:?} {} {}", amount, status == 1),
+                None => warn!("{account:?} undefined status"),
+            }
+            records.push(record);
+            tokio::time::sleep(Duration::from_secs(delay_in_seconds)).await;
+        }
+        Ok(records)
+    }
+
+    async fn distribute_one(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        fee: &TxFee,
+        record: &mut TxRecord,
+    ) -> Result<()> {
+        let (account, amount) = target;
+        let mut tx_object = TransactionParameters {
+            to: Some(*account),
+            value: *amount,
+            ..Default::default()
+        };
+        self.resolve_fee(fee).await?.apply(&mut tx_object);
+        // the previous tx is included, the pending nonce is the next one
+        tx_object.nonce = self.pending_nonce(record.source).await;
+        record.nonce = tx_object.nonce;
+        let signed = self
+            .sign_transaction(tx_object, source)
+            .await
+            .map_err(|e| self.decode_error(&e))?;
+
+        record.send_time = now_millis();
+        let hash = self.send_signed(Some(record.source), signed.raw_transaction).await?;
+        record.hash = Some(hash);
+
+        loop {
+            let receipt = self
+                .request(None, |web3| async move { web3.eth().transaction_receipt(hash).await })
+                .await
+                .map_err(|e| self.decode_error(&e))?;
+            match receipt {
+                Some(receipt) => {
+                    record.block = receipt.block_number.map(|n| n.as_u64());
+                    record.status = receipt.status.map(|s| s.as_u64());
+                    record.latency_ms = Some(now_millis().saturating_sub(record.send_time));
+                    break Ok(());
+                }
+                None => tokio::time::sleep(RECEIPT_POLL_INTERVAL).await,
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt::Formatter,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+use tracing::{debug, info};
+
+// the task latencies compared for a change of the limit
+const WINDOW_LEN: usize = 10;
+// latency changes within this range are noise
+const LATENCY_TOLERANCE: Duration = Duration::from_millis(100);
+
+/// The bounds and the start of a concurrency limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConcurrencyLimits {
+    pub min: u32,
+    pub max: u32,
+    pub initial: u32,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            min: 1,
+            max: 64,
+            initial: 2,
+        }
+    }
+}
+
+/// The statistics of the tasks run by a `ConcurrencyLimiter`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConcurrencyStats {
+    pub succeeded: u32,
+    pub failed: u32,
+    /// the total time of succeeded tasks, milliseconds
+    pub total_ms: u128,
+    /// the average time of succeeded tasks, milliseconds
+    pub average_ms: u128,
+    /// the wall time of the run, milliseconds
+    pub elapsed_ms: u128,
+    pub final_limit: u32,
+    pub peak_limit: u32,
+    /// the most tasks running at once
+    pub peak_running: u32,
+}
+
+impl std::fmt::Display for ConcurrencyStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "succeeded,{},failed,{},total_ms,{},average_ms,{},elapsed_ms,{},final_limit,{},peak_limit,{},peak_running,{}",
+            self.succeeded,
+            self.failed,
+            self.total_ms,
+            self.average_ms,
+            self.elapsed_ms,
+            self.final_limit,
+            self.peak_limit,
+            self.peak_running
+        )
+    }
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    limit: u32,
+    running: u32,
+    // latencies of succeeded tasks since the last adjustment, the oldest first
+    window: VecDeque<Duration>,
+    stats: ConcurrencyStats,
+}
+
+/// A limit of concurrent tasks, adjusted by AIMD on task latency.
+///
+/// Once a window of tasks has finished, the limit grows by one if the newer half
+/// is not slower than the older half, and is halved otherwise.
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    limits: ConcurrencyLimits,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+impl Default for ConcurrencyLimiter {
+    fn default() -> Self {
+        Self::new(ConcurrencyLimits::default())
+    }
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(limits: ConcurrencyLimits) -> Self {
+        let min = limits.min.max(1);
+        let max = limits.max.max(min);
+        let limits = ConcurrencyLimits {
+            min,
+            max,
+            initial: limits.initial.clamp(min, max),
+        };
+        Self {
+            limits,
+            state: Mutex::new(LimiterState {
+                limit: limits.initial,
+                running: 0,
+                window: VecDeque::with_capacity(WINDOW_LEN),
+                stats: ConcurrencyStats {
+                    peak_limit: limits.initial,
+                    ..Default::default()
+                },
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// current limit
+    pub fn limit(&self) -> u32 {
+        self.state.lock().unwrap().limit
+    }
+
+    // take a slot if one is free
+    fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.running >= state.limit {
+            return false;
+        }
+        state.running += 1;
+        state.stats.peak_running = state.stats.peak_running.max(state.running);
+        true
+    }
+
+    /// wait for a free slot
+    pub async fn acquire(&self) {
+        loop {
+            let released = self.released.notified();
+            if self.try_acquire() {
+                break;
+            }
+            released.await;
+        }
+    }
+
+    /// a task finished, `latency` is none if it failed
+    pub fn release(&self, latency: Option<Duration>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.running -= 1;
+            match latency {
+                Some(latency) => {
+                    state.stats.succeeded += 1;
+                    state.stats.total_ms += latency.as_millis();
+                    state.window.push_back(latency);
+                    if state.window.len() == WINDOW_LEN {
+                        self.adjust(&mut state);
+                    }
+                }
+                None => state.stats.failed += 1,
+            }
+        }
+        self.released.notify_waiters();
+    }
+
+    fn adjust(&self, state: &mut LimiterState) {
+        let half = state.window.len() / 2;
+        let older = state.window.iter().take(half).sum::<Duration>() / half as u32;
+        let newer = state.window.iter().skip(half).sum::<Duration>() / (state.window.len() - half) as u32;
+        let old = state.limit;
+        state.limit = if newer > older + LATENCY_TOLERANCE {
+            (old / 2).max(self.limits.min)
+        } else {
+            (old + 1).min(self.limits.max)
+        };
+        state.stats.peak_limit = state.stats.peak_limit.max(state.limit);
+        // samples under the new limit only
+        state.window.clear();
+        if state.limit != old {
+            debug!(
+                "concurrency limit {} -> {}, latency {:?} -> {:?}",
+                old, state.limit, older, newer
+            );
+        }
+    }
+
+    /// Run tasks within the limit, and return the statistics of the run.
+    ///
+    /// A task succeeds if it returns `Ok`, only succeeded tasks are timed.
+    pub async fn run<F, T>(self: Arc<Self>, tasks: Vec<F>) -> ConcurrencyStats
+    where
+        F: FnOnce() -> T,
+        T: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let start = Instant::now();
+        let mut handles = Vec::with_capacity(tasks.len());
+        for f in tasks {
+            self.acquire().await;
+            let fut = f();
+            let limiter = self.clone();
+            handles.push(tokio::spawn(async move {
+                let now = Instant::now();
+                let latency = fut.await.ok().map(|_| now.elapsed());
+                limiter.release(latency);
+            }));
+        }
+        for handle in handles {
+            // a panicked task failed without releasing its slot
+            if handle.await.is_err() {
+                self.release(None);
+            }
+        }
+
+        let mut stats = {
+            let state = self.state.lock().unwrap();
+            ConcurrencyStats {
+                final_limit: state.limit,
+                ..state.stats.clone()
+            }
+        };
+        stats.average_ms = stats.total_ms.checked_div(stats.succeeded as u128).unwrap_or_default();
+        stats.elapsed_ms = start.elapsed().as_millis();
+        info!("tasks summary: {}", stats);
+        stats
+    }
+}
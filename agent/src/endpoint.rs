@@ -0,0 +1,246 @@
+use crate::error::{Error, Result};
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+use tokio::runtime::Runtime;
+use tracing::{info, warn};
+use web3::{
+    transports::{Either, Http, WebSocket},
+    types::Address,
+};
+
+/// The transport of an endpoint, WebSocket for `ws://` and `wss://` urls, Http otherwise
+pub type Transport = Either<WebSocket, Http>;
+
+lazy_static::lazy_static! {
+    // WebSocket connections are driven by this runtime, they keep going while senders block their threads
+    static ref WS_RUNTIME: Runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("ws-transport")
+        .enable_all()
+        .build()
+        .unwrap();
+}
+
+fn is_ws(url: &str) -> bool {
+    url.starts_with("ws://") || url.starts_with("wss://")
+}
+
+fn connect_ws(url: &str) -> Result<WebSocket> {
+    let url = url.to_owned();
+    let handle = WS_RUNTIME.spawn(async move { WebSocket::new(&url).await });
+    web3::futures::executor::block_on(handle)
+        .map_err(|e| Error::Other(e.to_string()))?
+        .map_err(Error::from)
+}
+
+/// How requests are spread across the endpoints of a pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EndpointPolicy {
+    /// every request goes to the next endpoint
+    #[default]
+    RoundRobin,
+    /// requests of the same source account always go to the same endpoint
+    Sticky,
+    /// stick to one endpoint, switch to the next one if a request can't be sent
+    Failover,
+}
+
+impl std::fmt::Display for EndpointPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::RoundRobin => write!(f, "round-robin"),
+            Self::Sticky => write!(f, "sticky"),
+            Self::Failover => write!(f, "failover"),
+        }
+    }
+}
+
+impl std::str::FromStr for EndpointPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "round-robin" | "rr" => Ok(Self::RoundRobin),
+            "sticky" => Ok(Self::Sticky),
+            "failover" => Ok(Self::Failover),
+            n => Err(format!("invalid endpoint policy {n}")),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct EndpointStats {
+    success: AtomicU64,
+    failure: AtomicU64,
+    // total latency in milliseconds
+    latency: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct Endpoint {
+    pub url: String,
+    pub web3: web3::Web3<Transport>,
+    /// the WebSocket connection for subscriptions, `None` for http endpoints
+    pub ws: Option<WebSocket>,
+    stats: EndpointStats,
+}
+
+/// Per-endpoint request statistics
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EndpointReport {
+    pub url: String,
+    pub success: u64,
+    pub failure: u64,
+    pub avg_latency_ms: u64,
+}
+
+impl std::fmt::Display for EndpointReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.url, self.success, self.failure, self.avg_latency_ms
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct EndpointPool {
+    endpoints: Vec<Endpoint>,
+    policy: EndpointPolicy,
+    cursor: AtomicUsize,
+}
+
+impl EndpointPool {
+    /// connect to all `urls`, the first bad endpoint fails the pool
+    pub fn new(urls: &[String], timeout: Option<u64>, policy: EndpointPolicy) -> Result<Self> {
+        if urls.is_empty() {
+            return Err(Error::Other("at least one endpoint is required".to_string()));
+        }
+        let endpoints = urls
+            .iter()
+            .map(|url| {
+                let bad_endpoint = |e: String| Error::Other(format!("bad endpoint {url}: {e}"));
+                let (transport, ws) = if is_ws(url) {
+                    let ws = connect_ws(url).map_err(|e| bad_endpoint(e.to_string()))?;
+                    (Either::Left(ws.clone()), Some(ws))
+                } else {
+                    let client = Client::builder()
+                        .timeout(Duration::from_secs(timeout.unwrap_or(3)))
+                        .build()
+                        .map_err(|e| bad_endpoint(e.to_string()))?;
+                    let url = Url::parse(url).map_err(|e| bad_endpoint(e.to_string()))?;
+                    (Either::Right(Http::with_client(client, url)), None)
+                };
+                Ok(Endpoint {
+                    url: url.to_owned(),
+                    web3: web3::Web3::new(transport),
+                    ws,
+                    stats: EndpointStats::default(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            endpoints,
+            policy,
+            cursor: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    pub fn policy(&self) -> EndpointPolicy {
+        self.policy
+    }
+
+    pub fn get(&self, idx: usize) -> &Endpoint {
+        &self.endpoints[idx % self.endpoints.len()]
+    }
+
+    /// the first WebSocket connection of the pool, for subscriptions
+    pub fn ws(&self) -> Option<WebSocket> {
+        self.endpoints.iter().find_map(|ep| ep.ws.clone())
+    }
+
+    /// pick an endpoint for a request, `key` is the source account of the request if any
+    pub fn select(&self, key: Option<&Address>) -> usize {
+        let n = self.endpoints.len();
+        match (self.policy, key) {
+            (EndpointPolicy::Sticky, Some(key)) => {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                hasher.finish() as usize % n
+            }
+            (EndpointPolicy::Failover, _) => self.cursor.load(Ordering::Relaxed) % n,
+            _ => self.cursor.fetch_add(1, Ordering::Relaxed) % n,
+        }
+    }
+
+    /// switch to the next endpoint after `idx` failed, returns the new endpoint index
+    pub fn failover(&self, idx: usize) -> usize {
+        let n = self.endpoints.len();
+        let next = (idx + 1) % n;
+        if self
+            .cursor
+            .compare_exchange(idx, next, Ordering::AcqRel, Ordering::Relaxed)
+            .is_ok()
+        {
+            warn!(
+                "endpoint {} failed, switched to {}",
+                self.get(idx).url,
+                self.get(next).url
+            );
+        }
+        next
+    }
+
+    pub fn record(&self, idx: usize, ok: bool, elapsed: Duration) {
+        let stats = &self.get(idx).stats;
+        if ok {
+            stats.success.fetch_add(1, Ordering::Relaxed);
+        } else {
+            stats.failure.fetch_add(1, Ordering::Relaxed);
+        }
+        stats.latency.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn report(&self) -> Vec<EndpointReport> {
+        self.endpoints
+            .iter()
+            .map(|ep| {
+                let success = ep.stats.success.load(Ordering::Relaxed);
+                let failure = ep.stats.failure.load(Ordering::Relaxed);
+                let total = success + failure;
+                let avg_latency_ms = if total == 0 {
+                    0
+                } else {
+                    ep.stats.latency.load(Ordering::Relaxed) / total
+                };
+                EndpointReport {
+                    url: ep.url.clone(),
+                    success,
+                    failure,
+                    avg_latency_ms,
+                }
+            })
+            .collect()
+    }
+
+    pub fn log_report(&self) {
+        info!("Endpoint summary: url,success,failure,latency(ms)");
+        self.report().iter().for_each(|r| info!("{r}"));
+    }
+}
@@ -1,13 +1,22 @@
-use std::fmt::Formatter;
+use std::{
+    fmt::Formatter,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
     CheckTx,
-    SyncTx,
     SendErr,
     TxInternalErr(InternalError),
+    NonceTooLow(RpcError),
+    NonceTooHigh(RpcError),
+    InsufficientFunds(RpcError),
+    Underpriced(RpcError),
+    MempoolFull(RpcError),
+    Timeout(String),
+    Rpc(RpcError),
     Io(std::io::Error),
     Db(redis::RedisError),
     NotSupport(String),
@@ -22,13 +31,239 @@ pub enum InternalError {
     Other(String),
 }
 
+/// A JSON-RPC error returned by an endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<serde_json::Value>,
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.data {
+            Some(data) => write!(f, "code {} {} {}", self.code, self.message, data),
+            None => write!(f, "code {} {}", self.code, self.message),
+        }
+    }
+}
+
+/// Categories of errors, counted in test summaries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    NonceTooLow,
+    NonceTooHigh,
+    InsufficientFunds,
+    Underpriced,
+    MempoolFull,
+    Timeout,
+    CheckTx,
+    Send,
+    Rpc,
+    Other,
+}
+
+impl ErrorKind {
+    pub const ALL: [ErrorKind; 10] = [
+        ErrorKind::NonceTooLow,
+        ErrorKind::NonceTooHigh,
+        ErrorKind::InsufficientFunds,
+        ErrorKind::Underpriced,
+        ErrorKind::MempoolFull,
+        ErrorKind::Timeout,
+        ErrorKind::CheckTx,
+        ErrorKind::Send,
+        ErrorKind::Rpc,
+        ErrorKind::Other,
+    ];
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NonceTooLow => write!(f, "nonce-too-low"),
+            Self::NonceTooHigh => write!(f, "nonce-too-high"),
+            Self::InsufficientFunds => write!(f, "insufficient-funds"),
+            Self::Underpriced => write!(f, "underpriced"),
+            Self::MempoolFull => write!(f, "mempool-full"),
+            Self::Timeout => write!(f, "timeout"),
+            Self::CheckTx => write!(f, "check-tx"),
+            Self::Send => write!(f, "send"),
+            Self::Rpc => write!(f, "rpc"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+// JSON-RPC error codes of tx rejections, the message tells the reason
+// geth and frontier tx pool errors
+const CODE_SERVER_ERROR: i64 = -32000;
+// EIP-1474 transaction rejected
+const CODE_TX_REJECTED: i64 = -32003;
+// EIP-1474 limit exceeded, the node sheds load
+const CODE_LIMIT_EXCEEDED: i64 = -32005;
+// openethereum and nethermind transaction errors
+const CODE_TX_ERROR: i64 = -32010;
+// tendermint and findora report failed `broadcast_tx_sync` and check tx results as internal errors
+const CODE_INTERNAL_ERROR: i64 = -32603;
+
+impl Error {
+    /// classify an error returned by an endpoint by its code, and by its message within a code
+    pub fn from_rpc(code: i64, message: String, data: Option<serde_json::Value>) -> Self {
+        let text = match &data {
+            Some(data) => format!("{message} {data}").to_lowercase(),
+            None => message.to_lowercase(),
+        };
+        let rpc = RpcError { code, message, data };
+        match code {
+            CODE_LIMIT_EXCEEDED => Error::MempoolFull(rpc),
+            CODE_SERVER_ERROR | CODE_TX_REJECTED | CODE_TX_ERROR => Error::from_message(rpc, &text),
+            CODE_INTERNAL_ERROR => match Error::from_message(rpc, &text) {
+                Error::Rpc(rpc) => Error::TxInternalErr(InternalError::Other(rpc.to_string())),
+                e => e,
+            },
+            // no code, the error is known by its text only
+            0 => Error::from_message(rpc, &text),
+            _ => Error::Rpc(rpc),
+        }
+    }
+
+    // classify a tx rejection by its lowercase message and data
+    fn from_message(rpc: RpcError, text: &str) -> Self {
+        if text.contains("nonce too low") || text.contains("nonce is too low") {
+            Error::NonceTooLow(rpc)
+        } else if text.contains("nonce too high") || text.contains("nonce is too high") {
+            Error::NonceTooHigh(rpc)
+        } else if text.contains("invalidnonce") || text.contains("invalid nonce") {
+            match nonce_direction(text) {
+                Some(true) => Error::NonceTooLow(rpc),
+                Some(false) => Error::NonceTooHigh(rpc),
+                None => Error::TxInternalErr(InternalError::InvalidNonce(rpc.to_string())),
+            }
+        } else if text.contains("insufficient funds") || text.contains("insufficient balance") {
+            Error::InsufficientFunds(rpc)
+        } else if text.contains("underpriced") || text.contains("gas price too low") || text.contains("fee too low") {
+            Error::Underpriced(rpc)
+        } else if text.contains("broadcast_tx_sync")
+            || text.contains("mempool is full")
+            || text.contains("pool is full")
+        {
+            Error::MempoolFull(rpc)
+        } else if text.contains("transaction check error") {
+            Error::CheckTx
+        } else if text.contains("internalerror") {
+            Error::TxInternalErr(InternalError::Other(rpc.to_string()))
+        } else {
+            Error::Rpc(rpc)
+        }
+    }
+
+    /// classify an error by its text only, when no JSON-RPC error is available
+    pub fn from_text(text: String) -> Self {
+        let lower = text.to_lowercase();
+        if lower.contains("timed out") || lower.contains("timeout") {
+            Error::Timeout(text)
+        } else if lower.contains("error sending request") {
+            Error::SendErr
+        } else {
+            match Error::from_rpc(0, text, None) {
+                Error::Rpc(rpc) => Error::Other(rpc.message),
+                e => e,
+            }
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::NonceTooLow(_) => ErrorKind::NonceTooLow,
+            Error::NonceTooHigh(_) => ErrorKind::NonceTooHigh,
+            Error::InsufficientFunds(_) => ErrorKind::InsufficientFunds,
+            Error::Underpriced(_) => ErrorKind::Underpriced,
+            Error::MempoolFull(_) => ErrorKind::MempoolFull,
+            Error::Timeout(_) => ErrorKind::Timeout,
+            Error::CheckTx => ErrorKind::CheckTx,
+            Error::SendErr => ErrorKind::Send,
+            Error::Rpc(_) | Error::TxInternalErr(_) => ErrorKind::Rpc,
+            _ => ErrorKind::Other,
+        }
+    }
+
+    /// the same tx could be accepted if sent again later
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Error::MempoolFull(_) | Error::Timeout(_) | Error::SendErr | Error::Underpriced(_)
+        )
+    }
+
+    /// the local nonce is out of sync with the node
+    pub fn is_nonce_error(&self) -> bool {
+        matches!(
+            self,
+            Error::NonceTooLow(_) | Error::NonceTooHigh(_) | Error::TxInternalErr(InternalError::InvalidNonce(_))
+        )
+    }
+}
+
+// `Some(true)` if the nonce of an `InvalidNonce` error is lower than expected,
+// the message looks like "InvalidNonce: expected 5, got 3"
+fn nonce_direction(text: &str) -> Option<bool> {
+    let number_after = |key: &str| -> Option<u64> {
+        let rest = &text[text.find(key)? + key.len()..];
+        let digits = rest
+            .trim_start_matches(|c: char| !c.is_ascii_digit())
+            .split(|c: char| !c.is_ascii_digit())
+            .next()?;
+        digits.parse().ok()
+    };
+    let expected = number_after("expected")?;
+    let got = number_after("got")?;
+    Some(got < expected)
+}
+
+/// Count errors by `ErrorKind`
+#[derive(Debug, Default)]
+pub struct ErrorCounters {
+    counts: [AtomicU64; 10],
+}
+
+impl ErrorCounters {
+    pub fn add(&self, kind: ErrorKind) {
+        self.counts[kind as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, kind: ErrorKind) -> u64 {
+        self.counts[kind as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn total(&self) -> u64 {
+        ErrorKind::ALL.iter().map(|k| self.get(*k)).sum()
+    }
+}
+
+impl std::fmt::Display for ErrorCounters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let counts = ErrorKind::ALL
+            .iter()
+            .map(|k| format!("{},{}", k, self.get(*k)))
+            .collect::<Vec<_>>();
+        write!(f, "{}", counts.join(","))
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::CheckTx => write!(f, "tx check failed"),
-            Error::SyncTx => write!(f, "tx not accepted by tendermint"),
             Error::SendErr => write!(f, "tx not sent"),
             Error::TxInternalErr(e) => write!(f, "Internal Error:: {e:?}"),
+            Error::NonceTooLow(e) => write!(f, "nonce too low: {e}"),
+            Error::NonceTooHigh(e) => write!(f, "nonce too high: {e}"),
+            Error::InsufficientFunds(e) => write!(f, "insufficient funds: {e}"),
+            Error::Underpriced(e) => write!(f, "tx underpriced: {e}"),
+            Error::MempoolFull(e) => write!(f, "tx not accepted by tendermint: {e}"),
+            Error::Timeout(e) => write!(f, "request timeout: {e}"),
+            Error::Rpc(e) => write!(f, "rpc error: {e}"),
             Error::Io(e) => write!(f, "Io error {e:?}"),
             Error::Db(e) => write!(f, "Database error {e:?}"),
             Error::NotSupport(e) => write!(f, "Not support: {e}"),
@@ -60,3 +295,96 @@ impl From<redis::RedisError> for Error {
         Self::Db(e)
     }
 }
+
+impl From<web3::Error> for Error {
+    fn from(e: web3::Error) -> Self {
+        match e {
+            web3::Error::Rpc(e) => Error::from_rpc(e.code.code(), e.message, e.data),
+            e => Error::from_text(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // the error object of a JSON-RPC response
+    fn rpc_error(payload: serde_json::Value) -> Error {
+        let code = payload["code"].as_i64().unwrap();
+        let message = payload["message"].as_str().unwrap().to_string();
+        Error::from_rpc(code, message, payload.get("data").cloned())
+    }
+
+    #[test]
+    fn geth_tx_pool_errors() {
+        let err = rpc_error(json!({"code": -32000, "message": "nonce too low"}));
+        assert_eq!(err.kind(), ErrorKind::NonceTooLow);
+        let err = rpc_error(json!({"code": -32000, "message": "insufficient funds for gas * price + value"}));
+        assert_eq!(err.kind(), ErrorKind::InsufficientFunds);
+        let err = rpc_error(json!({"code": -32000, "message": "replacement transaction underpriced"}));
+        assert_eq!(err.kind(), ErrorKind::Underpriced);
+        let err = rpc_error(json!({"code": -32000, "message": "txpool is full"}));
+        assert_eq!(err.kind(), ErrorKind::MempoolFull);
+        let err = rpc_error(json!({"code": -32000, "message": "execution reverted"}));
+        assert_eq!(err.kind(), ErrorKind::Rpc);
+    }
+
+    #[test]
+    fn eip1474_and_parity_codes() {
+        let err = rpc_error(json!({"code": -32005, "message": "limit exceeded"}));
+        assert_eq!(err.kind(), ErrorKind::MempoolFull);
+        let err = rpc_error(json!({
+            "code": -32010,
+            "message": "Transaction nonce is too low. Try incrementing the nonce."
+        }));
+        assert_eq!(err.kind(), ErrorKind::NonceTooLow);
+        let err = rpc_error(json!({"code": -32003, "message": "transaction underpriced"}));
+        assert_eq!(err.kind(), ErrorKind::Underpriced);
+    }
+
+    #[test]
+    fn internal_errors() {
+        let err = rpc_error(json!({
+            "code": -32603,
+            "message": "Internal error",
+            "data": "mempool is full: number of txs 5000 (max: 5000), total txs bytes 2495128 (max: 1073741824)"
+        }));
+        assert_eq!(err.kind(), ErrorKind::MempoolFull);
+        let err = rpc_error(json!({"code": -32603, "message": "InternalError: InvalidNonce: expected 5, got 3"}));
+        assert_eq!(err.kind(), ErrorKind::NonceTooLow);
+        let err = rpc_error(json!({"code": -32603, "message": "InternalError: InvalidNonce: expected 5, got 7"}));
+        assert_eq!(err.kind(), ErrorKind::NonceTooHigh);
+        let err = rpc_error(json!({"code": -32603, "message": "Transaction check error"}));
+        assert_eq!(err.kind(), ErrorKind::CheckTx);
+        let err = rpc_error(json!({"code": -32603, "message": "Internal error"}));
+        assert!(matches!(err, Error::TxInternalErr(InternalError::Other(_))));
+    }
+
+    #[test]
+    fn other_codes_are_not_tx_errors() {
+        // the message of a bad request is not a tx rejection, whatever it says
+        let err = rpc_error(json!({"code": -32602, "message": "invalid argument 0: nonce too low"}));
+        assert_eq!(err.kind(), ErrorKind::Rpc);
+        let err = rpc_error(json!({"code": -32601, "message": "the method eth_foo does not exist/is not available"}));
+        assert_eq!(err.kind(), ErrorKind::Rpc);
+    }
+
+    #[test]
+    fn text_errors() {
+        assert_eq!(
+            Error::from_text("operation timed out".to_string()).kind(),
+            ErrorKind::Timeout
+        );
+        assert_eq!(
+            Error::from_text("error sending request for url (http://localhost:8545/)".to_string()).kind(),
+            ErrorKind::Send
+        );
+        assert_eq!(
+            Error::from_text("broadcast_tx_sync failed".to_string()).kind(),
+            ErrorKind::MempoolFull
+        );
+        assert_eq!(Error::from_text("unexpected eof".to_string()).kind(), ErrorKind::Other);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    client::AsyncTestClient,
+    error::{Error, Result},
+};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::debug;
+use web3::types::{BlockId, BlockNumber, U256, U64};
+
+// the shortest interval between two block number checks of a cached gas price
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The default number of recent blocks a percentile is taken over
+pub const DEFAULT_PERCENTILE_BLOCKS: u64 = 20;
+
+/// How to price the gas of a transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GasStrategy {
+    /// the same gas price for all txs, wei
+    Fixed(U256),
+    /// the gas price reported by the node, asked for each tx
+    #[default]
+    Node,
+    /// the gas price reported by the node, asked again every N blocks
+    Refresh(u64),
+    /// a percentile (0-100) of tx gas prices in the last N blocks, never below the node's price
+    Percentile(u8, u64),
+}
+
+impl std::fmt::Display for GasStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Fixed(price) => write!(f, "fixed:{price}"),
+            Self::Node => write!(f, "node"),
+            Self::Refresh(blocks) => write!(f, "refresh:{blocks}"),
+            Self::Percentile(p, blocks) => write!(f, "percentile:{p}:{blocks}"),
+        }
+    }
+}
+
+impl std::str::FromStr for GasStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        let segs = s.trim().split(':').collect::<Vec<_>>();
+        let num = |n: &str| n.parse::<u64>().map_err(|e| format!("invalid gas strategy {s}: {e}"));
+        match segs.as_slice() {
+            ["fixed", price] => U256::from_dec_str(price)
+                .map(Self::Fixed)
+                .map_err(|e| format!("invalid gas strategy {s}: {e:?}")),
+            ["node"] => Ok(Self::Node),
+            ["refresh", blocks] => Ok(Self::Refresh(num(blocks)?.max(1))),
+            ["percentile", p] | ["percentile", p, _] => {
+                let p = num(p)?;
+                if p > 100 {
+                    return Err(format!("invalid gas strategy {s}: percentile over 100"));
+                }
+                let blocks = match segs.get(2) {
+                    Some(blocks) => num(blocks)?.max(1),
+                    None => DEFAULT_PERCENTILE_BLOCKS,
+                };
+                Ok(Self::Percentile(p as u8, blocks))
+            }
+            _ => Err(format!("invalid gas strategy {s}")),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CachedPrice {
+    price: U256,
+    // the block the price was taken at
+    height: U64,
+    // the last time the block number was checked
+    checked: Instant,
+}
+
+/// Gas prices following a `GasStrategy`, shared by all senders of a test
+#[derive(Debug)]
+pub struct GasOracle {
+    strategy: GasStrategy,
+    // locked only to read or write the cached price, never across a request
+    cache: std::sync::Mutex<Option<CachedPrice>>,
+    // held by the only task refreshing the cached price
+    refresh: Mutex<()>,
+}
+
+impl GasOracle {
+    pub fn new(strategy: GasStrategy) -> Self {
+        Self {
+            strategy,
+            cache: std::sync::Mutex::new(None),
+            refresh: Mutex::new(()),
+        }
+    }
+
+    pub fn strategy(&self) -> GasStrategy {
+        self.strategy
+    }
+
+    /// the gas price of the next tx
+    pub async fn gas_price(&self, client: &AsyncTestClient) -> Result<U256> {
+        match self.strategy {
+            GasStrategy::Fixed(price) => Ok(price),
+            GasStrategy::Node => node_price(client).await,
+            GasStrategy::Refresh(blocks) | GasStrategy::Percentile(_, blocks) => {
+                if let Some(price) = self.checked_price() {
+                    return Ok(price);
+                }
+                let _refresh = match self.refresh.try_lock() {
+                    Ok(guard) => guard,
+                    Err(_) => match self.cached_price() {
+                        // another task is refreshing it, the cached price is good enough meanwhile
+                        Some((price, _)) => return Ok(price),
+                        None => self.refresh.lock().await,
+                    },
+                };
+                // the price may be refreshed while waiting for the lock
+                if let Some(price) = self.checked_price() {
+                    return Ok(price);
+                }
+
+                let cached = self.cached_price();
+                let height = block_number(client).await?;
+                if let Some((price, cached_height)) = cached {
+                    // a percentile follows every new block, a refreshed price every `blocks`
+                    let period = match self.strategy {
+                        GasStrategy::Refresh(_) => blocks,
+                        _ => 1,
+                    };
+                    if height < cached_height + period {
+                        if let Some(cached) = self.cache.lock().unwrap().as_mut() {
+                            cached.checked = Instant::now();
+                        }
+                        return Ok(price);
+                    }
+                }
+                let price = match self.strategy {
+                    GasStrategy::Percentile(p, blocks) => percentile_price(client, height, p, blocks).await?,
+                    _ => node_price(client).await?,
+                };
+                if let Some((old, _)) = cached {
+                    if old != price {
+                        debug!("gas price {} -> {} at {}", old, price, height);
+                    }
+                }
+                *self.cache.lock().unwrap() = Some(CachedPrice {
+                    price,
+                    height,
+                    checked: Instant::now(),
+                });
+                Ok(price)
+            }
+        }
+    }
+
+    // the cached price if its block number was checked within `CHECK_INTERVAL`
+    fn checked_price(&self) -> Option<U256> {
+        self.cache
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|cached| cached.checked.elapsed() < CHECK_INTERVAL)
+            .map(|cached| cached.price)
+    }
+
+    fn cached_price(&self) -> Option<(U256, U64)> {
+        self.cache
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|cached| (cached.price, cached.height))
+    }
+}
+
+async fn node_price(client: &AsyncTestClient) -> Result<U256> {
+    client
+        .gas_price()
+        .await
+        .ok_or_else(|| Error::Other("failed to get gas price".to_string()))
+}
+
+async fn block_number(client: &AsyncTestClient) -> Result<U64> {
+    client
+        .block_number()
+        .await
+        .ok_or_else(|| Error::Other("failed to get block number".to_string()))
+}
+
+async fn percentile_price(client: &AsyncTestClient, height: U64, p: u8, blocks: u64) -> Result<U256> {
+    let floor = node_price(client).await?;
+    let mut prices = vec![];
+    // the latest block at least, `blocks` may be 0 if the strategy is built in code
+    for n in height.as_u64().saturating_sub(blocks.saturating_sub(1))..=height.as_u64() {
+        let id = BlockId::Number(BlockNumber::Number(n.into()));
+        if let Some(block) = client.block_with_txs(id).await {
+            prices.extend(block.transactions.iter().filter_map(|tx| tx.gas_price));
+        }
+    }
+    if prices.is_empty() {
+        return Ok(floor);
+    }
+    prices.sort();
+    let idx = (prices.len() - 1) * p as usize / 100;
+    Ok(prices[idx].max(floor))
+}
@@ -0,0 +1,110 @@
+use crate::{
+    error::{Error, Result},
+    keystore,
+    native::{restore_fra_keypair_at, XfrKeyPair},
+    KeyPair,
+};
+use bip0039::{Count, Language, Mnemonic};
+use bip32::{DerivationPath, XPrv};
+use libsecp256k1::{PublicKey, SecretKey};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::{ops::Range, path::Path, str::FromStr};
+use web3::types::{Address, H160, H256};
+
+/// The BIP-44 path of the `index`th ethereum account
+pub fn eth_path(index: u32) -> String {
+    format!("m/44'/60'/0'/0/{index}")
+}
+
+/// derive the ethereum key at `path` from a BIP-39 seed
+pub fn derive_eth_key(seed: &[u8], path: &str) -> Result<(SecretKey, H160)> {
+    let path = DerivationPath::from_str(path).map_err(|e| Error::Other(e.to_string()))?;
+    let ext = XPrv::derive_from_path(seed, &path).map_err(|e| Error::Other(e.to_string()))?;
+
+    let secret = SecretKey::parse_slice(&ext.to_bytes()).map_err(|e| Error::Other(e.to_string()))?;
+    let public = PublicKey::from_secret_key(&secret);
+
+    let mut res = [0u8; 64];
+    res.copy_from_slice(&public.serialize()[1..65]);
+    let public = H160::from(H256::from_slice(Keccak256::digest(res).as_slice()));
+
+    Ok((secret, public))
+}
+
+/// The `index`th target address of `source` in a run seeded by `seed`.
+///
+/// Targets only receive tokens, so they are hashed instead of derived, nobody holds their keys.
+pub fn target_address(seed: u64, source: &Address, index: u64) -> Address {
+    let mut hasher = Keccak256::new();
+    hasher.update(seed.to_be_bytes());
+    hasher.update(source.as_bytes());
+    hasher.update(index.to_be_bytes());
+    Address::from(H256::from_slice(hasher.finalize().as_slice()))
+}
+
+/// Keys derived from one master mnemonic, the `i`th key is at `m/44'/<coin>'/0'/0/i`.
+///
+/// It's saved in place of a list of keys, so a key file is only a phrase and an index range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HdKeys {
+    pub mnemonic: String,
+    pub start: u32,
+    pub count: u32,
+}
+
+impl HdKeys {
+    /// keys of a new 12-word ethereum mnemonic
+    pub fn generate(start: u32, count: u32) -> Self {
+        let mnemonic = Mnemonic::generate_in(Language::English, Count::Words12);
+        Self {
+            mnemonic: mnemonic.phrase().to_owned(),
+            start,
+            count,
+        }
+    }
+
+    pub fn range(&self) -> Range<u32> {
+        self.start..self.start + self.count
+    }
+
+    /// ethereum keys of the range, in order of indexes
+    pub fn eth_keys(&self) -> Result<Vec<KeyPair>> {
+        let mnemonic = Mnemonic::from_phrase_in(Language::English, self.mnemonic.trim())
+            .map_err(|e| Error::Other(e.to_string()))?;
+        let seed = mnemonic.to_seed("");
+        self.range()
+            .into_par_iter()
+            .map(|i| derive_eth_key(&seed, &eth_path(i)).map(|(secret, public)| KeyPair::new(&secret, public)))
+            .collect()
+    }
+
+    /// FRA keys of the range, in order of indexes
+    pub fn fra_keys(&self) -> Result<Vec<XfrKeyPair>> {
+        self.range()
+            .into_par_iter()
+            .map(|i| restore_fra_keypair_at(self.mnemonic.trim(), i))
+            .collect()
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let data = keystore::read_to_string(path)?;
+        serde_json::from_str(&data).map_err(|e| Error::Other(e.to_string()))
+    }
+
+    /// write the keys to `path`, as a keystore encrypted by `password` if any
+    pub fn write<P: AsRef<Path>>(&self, path: P, password: Option<&str>) -> Result<()> {
+        let data = serde_json::to_string(self).map_err(|e| Error::Other(e.to_string()))?;
+        keystore::write_secret(path, data.as_bytes(), password)
+    }
+}
+
+/// Read an ethereum key file, either `HdKeys` or a list of `KeyPair`, plain or in a keystore
+pub fn read_eth_keys<P: AsRef<Path>>(path: P) -> Result<Vec<KeyPair>> {
+    let data = keystore::read_to_string(path)?;
+    match serde_json::from_str::<HdKeys>(&data) {
+        Ok(keys) => keys.eth_keys(),
+        Err(_) => serde_json::from_str(&data).map_err(|e| Error::Other(e.to_string())),
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    endpoint::EndpointPool,
+    error::{Error, Result},
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::watch;
+use tracing::{info, warn};
+use web3::futures::StreamExt;
+
+// how often the block number is polled without a `newHeads` subscription
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// how long to wait for the first height
+const START_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The latest block height of a pool.
+///
+/// It follows `eth_subscribe("newHeads")` if the pool has a WebSocket endpoint,
+/// and falls back to polling `eth_blockNumber` otherwise.
+#[derive(Debug, Clone)]
+pub struct HeadWatcher {
+    height: watch::Receiver<u64>,
+    subscribed: Arc<AtomicBool>,
+}
+
+impl HeadWatcher {
+    /// start watching on a background thread, it returns once the current height is known.
+    ///
+    /// Fails if the current height can't be fetched in time.
+    pub fn start(pool: Arc<EndpointPool>) -> Result<Self> {
+        let (sender, height) = watch::channel(0u64);
+        let (started, start) = std::sync::mpsc::channel();
+        let subscribed = Arc::new(AtomicBool::new(false));
+        let flag = subscribed.clone();
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(async move {
+                let first = pool.get(pool.select(None)).web3.eth().block_number();
+                let first = match tokio::time::timeout(START_TIMEOUT, first).await {
+                    Ok(Ok(first)) => first.as_u64(),
+                    Ok(Err(e)) => {
+                        let _ = started.send(Err(Error::from(e)));
+                        return;
+                    }
+                    Err(_) => {
+                        let _ = started.send(Err(Error::Timeout("no block height to start with".to_string())));
+                        return;
+                    }
+                };
+                // a chain at height 0 starts too
+                if sender.send(first).is_err() || started.send(Ok(())).is_err() {
+                    return;
+                }
+                watch_heads(pool, sender, flag).await
+            });
+        });
+        start
+            .recv()
+            .map_err(|_| Error::Other("head watcher stopped".to_string()))??;
+        Ok(Self { height, subscribed })
+    }
+
+    /// the latest height
+    pub fn height(&self) -> u64 {
+        *self.height.borrow()
+    }
+
+    /// heights come from a `newHeads` subscription
+    pub fn is_subscribed(&self) -> bool {
+        self.subscribed.load(Ordering::Relaxed)
+    }
+
+    /// wait until the chain reaches `height`, returns the latest height
+    pub async fn wait_for(&self, height: u64) -> u64 {
+        let mut rx = self.height.clone();
+        loop {
+            let current = *rx.borrow();
+            if current >= height || rx.changed().await.is_err() {
+                break current;
+            }
+        }
+    }
+
+    /// wait until the chain reaches `height`, blocking the current thread
+    pub fn wait_for_blocking(&self, height: u64) -> u64 {
+        web3::futures::executor::block_on(self.wait_for(height))
+    }
+}
+
+async fn watch_heads(pool: Arc<EndpointPool>, sender: watch::Sender<u64>, subscribed: Arc<AtomicBool>) {
+    if let Some(ws) = pool.ws() {
+        match web3::Web3::new(ws).eth_subscribe().subscribe_new_heads().await {
+            Ok(mut heads) => {
+                info!("block pacing follows newHeads");
+                subscribed.store(true, Ordering::Relaxed);
+                // the subscription only notifies new blocks
+                if let Ok(height) = pool.get(0).web3.eth().block_number().await {
+                    if sender.send(height.as_u64()).is_err() {
+                        return;
+                    }
+                }
+                while let Some(head) = heads.next().await {
+                    match head.map(|h| h.number) {
+                        Ok(Some(number)) => {
+                            if sender.send(number.as_u64()).is_err() {
+                                return;
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            warn!("newHeads subscription failed: {:?}", e);
+                            break;
+                        }
+                    }
+                }
+                subscribed.store(false, Ordering::Relaxed);
+            }
+            Err(e) => warn!("failed to subscribe newHeads: {:?}", e),
+        }
+        warn!("block pacing falls back to polling");
+    }
+
+    loop {
+        let res = pool.get(pool.select(None)).web3.eth().block_number().await;
+        if let Ok(height) = res {
+            if height.as_u64() > *sender.borrow() && sender.send(height.as_u64()).is_err() {
+                return;
+            }
+        }
+        if sender.is_closed() {
+            return;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
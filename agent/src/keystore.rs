@@ -0,0 +1,117 @@
+use crate::error::{Error, Result};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+/// The env var of the passphrase of all keystores, it's prompted for if not set
+pub const PASSWORD_ENV: &str = "FETH_KEYSTORE_PASSWORD";
+
+lazy_static::lazy_static! {
+    // passphrases prompted for, a keystore is only asked once in a run
+    static ref PASSWORDS: Mutex<HashMap<PathBuf, String>> = Mutex::new(HashMap::new());
+}
+
+/// `path` is a Web3 Secret Storage (keystore v3) file
+pub fn is_keystore<P: AsRef<Path>>(path: P) -> bool {
+    std::fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<serde_json::Value>(&data).ok())
+        .map(|v| v.get("crypto").or_else(|| v.get("Crypto")).is_some())
+        .unwrap_or_default()
+}
+
+/// the passphrase of the keystore `path`, from `FETH_KEYSTORE_PASSWORD` or a prompt
+pub fn passphrase<P: AsRef<Path>>(path: P) -> Result<String> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(password);
+    }
+    let path = path.as_ref().to_path_buf();
+    let mut passwords = PASSWORDS.lock().unwrap();
+    if let Some(password) = passwords.get(&path) {
+        return Ok(password.clone());
+    }
+    let password = rpassword::prompt_password(format!("passphrase of {}: ", path.display()))?;
+    passwords.insert(path, password.clone());
+    Ok(password)
+}
+
+/// a passphrase of the new keystore `path`, from `FETH_KEYSTORE_PASSWORD` or a prompt typed twice
+pub fn new_passphrase<P: AsRef<Path>>(path: P) -> Result<String> {
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(password);
+    }
+    let path = path.as_ref();
+    let password = rpassword::prompt_password(format!("new passphrase of {}: ", path.display()))?;
+    let confirmed = rpassword::prompt_password("repeat the passphrase: ")?;
+    if password != confirmed {
+        return Err(Error::Other("passphrases do not match".to_string()));
+    }
+    PASSWORDS.lock().unwrap().insert(path.to_path_buf(), password.clone());
+    Ok(password)
+}
+
+/// read a secret file, it's decrypted if it's a keystore
+pub fn read_secret<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let path = path.as_ref();
+    if !is_keystore(path) {
+        return std::fs::read(path).map_err(Error::from);
+    }
+    let password = passphrase(path)?;
+    eth_keystore::decrypt_key(path, password).map_err(|e| {
+        // a wrong passphrase is asked again next time
+        PASSWORDS.lock().unwrap().remove(path);
+        Error::Other(format!("failed to decrypt {}: {}", path.display(), e))
+    })
+}
+
+/// read a text secret file, it's decrypted if it's a keystore
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String> {
+    String::from_utf8(read_secret(path)?).map_err(|e| Error::Other(e.to_string()))
+}
+
+/// write `secret` to `path`, as a keystore encrypted by `password` if any
+pub fn write_secret<P: AsRef<Path>>(path: P, secret: &[u8], password: Option<&str>) -> Result<()> {
+    let path = path.as_ref();
+    let password = match password {
+        Some(password) => password,
+        None => return std::fs::write(path, secret).map_err(Error::from),
+    };
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| Error::Other(format!("invalid keystore path {}", path.display())))?;
+    eth_keystore::encrypt_key(dir, &mut rand::thread_rng(), secret, password, Some(name))
+        .map(|_| ())
+        .map_err(|e| Error::Other(e.to_string()))
+}
+
+/// Encrypt the secret file `src` to the keystore `dst`.
+///
+/// A hex private key is stored as raw bytes, so the keystore works with other Ethereum tools.
+pub fn encrypt_file<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q, password: &str) -> Result<()> {
+    let data = std::fs::read(src)?;
+    let secret = std::str::from_utf8(&data)
+        .ok()
+        .map(|s| s.trim())
+        .filter(|s| secp256k1::SecretKey::from_str(s).is_ok())
+        .and_then(|s| hex::decode(s).ok())
+        .unwrap_or(data);
+    write_secret(dst, &secret, Some(password))
+}
+
+/// Decrypt the keystore `src` to the plaintext file `dst`, raw private keys are written in hex.
+pub fn decrypt_file<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<()> {
+    let secret = read_secret(src)?;
+    let data = match secp256k1::SecretKey::from_slice(&secret) {
+        Ok(_) => hex::encode(&secret).into_bytes(),
+        Err(_) => secret,
+    };
+    write_secret(dst, &data, None)
+}
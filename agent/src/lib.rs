@@ -1,55 +1,71 @@
+pub mod abi;
+pub mod bench;
+pub mod client;
+pub mod concurrency;
 pub mod db;
+pub mod endpoint;
 pub mod error;
+pub mod gas;
+pub mod hd;
+pub mod heads;
+pub mod keystore;
+pub mod logs;
+pub mod manifest;
+pub mod metrics;
 pub mod native;
+pub mod nonce;
+pub mod presign;
 pub mod profiler;
+pub mod rate;
+pub mod tracker;
+pub mod tx;
 pub mod utils;
 
+pub use bench::BenchContract;
+pub use client::{AsyncTestClient, CallOutcome};
+pub use concurrency::{ConcurrencyLimiter, ConcurrencyLimits, ConcurrencyStats};
+pub use endpoint::{EndpointPolicy, Transport};
+pub use gas::{GasOracle, GasStrategy};
+pub use heads::HeadWatcher;
+pub use logs::{DecodedLog, LogQuery};
+pub use manifest::{DeployedContract, Manifest};
+pub use metrics::{MetricsFormat, MetricsWriter, TxRecord};
+pub use nonce::NonceManager;
+pub use presign::RawTx;
+pub use rate::RateController;
+pub use tracker::{ReceiptTracker, TrackerReport};
+pub use tx::{TxFee, TxType};
+
 use crate::{
-    error::{Error, InternalError, Result},
-    utils::extract_keypair_from_file,
+    error::{Error, Result},
+    metrics::now_millis,
 };
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use bip0039::{Count, Language, Mnemonic};
-use bip32::{DerivationPath, XPrv};
-use lazy_static::lazy_static;
-use libsecp256k1::{PublicKey, SecretKey};
-use reqwest::{Client, Url};
+use libsecp256k1::SecretKey;
 use secp256k1::SecretKey as SecretKey2;
 use tracing::{debug, error, info, warn};
 
 use serde::{Deserialize, Serialize};
-use sha3::{Digest, Keccak256};
 use std::{
     cell::RefCell,
     error::Error as StdError,
     fs,
-    future::Future,
     ops::AddAssign,
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
-    sync::{
-        atomic::{AtomicU32, AtomicUsize, Ordering},
-        Arc,
-    },
-    time,
+    sync::Arc,
     time::Duration,
 };
 
-use tokio::{
-    runtime::Runtime,
-    sync::{mpsc::Receiver, Mutex},
-    task,
-};
+use tokio::{runtime::Runtime, sync::Mutex};
 use web3::{
     self,
     api::Eth,
-    contract::{tokens::Tokenizable, Contract, Options},
-    ethabi::{Int, Token, Uint},
-    futures::TryFutureExt,
-    transports::Http,
+    signing::{Key, SecretKeyRef},
     types::{
-        Address, Block, BlockId, BlockNumber, Bytes, Transaction, TransactionId, TransactionParameters,
-        TransactionReceipt, H160, H256, U128, U256, U64,
+        AccessList, Address, Block, BlockId, BlockNumber, Bytes, CallRequest, Transaction, TransactionId,
+        TransactionParameters, TransactionReceipt, H160, H256, U256, U64,
     },
 };
 
@@ -61,46 +77,31 @@ pub const BLOCK_TIME: u64 = 16;
 const WEB3_SRV: &str = "https://prod-testnet.prod.findora.org:8545";
 //const WEB3_SRV: &str = "https://dev-mainnetmock.dev.findora.org:8545";
 
-lazy_static! {
-    pub(crate) static ref CUR_TASKS: Arc<AtomicU32> = Arc::new(AtomicU32::new(0));
-    pub(crate) static ref MAX_TASKS: Arc<AtomicU32> = Arc::new(AtomicU32::new(2));
-    // total success tasks、total tasks cost time、average tasks cost time queue
-    pub(crate) static ref RES_QUEUE_SECS: Arc<Mutex<(u32, u128, Vec::<u128>)>> = Arc::new(Mutex::new((0, 0, Vec::new())));
-
-}
-
-const PULL_INTERVAL: u64 = 50;
-const RES_QUEUE_MAX_LEN: usize = 10;
-const UPDATE_INTERVAL: u64 = 300;
-const DELTA_RANGE: u128 = 100;
-
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct KeyPair {
     pub address: String,
     pub private: String,
 }
 
+impl KeyPair {
+    pub fn new(secret: &SecretKey, public: H160) -> Self {
+        KeyPair {
+            address: eth_checksum::checksum(&format!("{public:?}")),
+            private: hex::encode(secret.serialize()),
+        }
+    }
+}
+
 #[inline(always)]
 pub fn one_eth_key() -> KeyPair {
     let (_, secret, public) = gen_one_eth_key();
 
-    KeyPair {
-        address: eth_checksum::checksum(&format!("{public:?}")),
-        private: hex::encode(secret.serialize()),
-    }
+    KeyPair::new(&secret, public)
 }
 
 pub fn gen_one_eth_key() -> (Mnemonic, SecretKey, H160) {
     let mnemonic = Mnemonic::generate_in(Language::English, Count::Words12);
-    let bs = &mnemonic.to_seed("");
-    let ext = XPrv::derive_from_path(bs, &DerivationPath::from_str("m/44'/60'/0'/0/0").unwrap()).unwrap();
-
-    let secret = SecretKey::parse_slice(&ext.to_bytes()).unwrap();
-    let public = PublicKey::from_secret_key(&secret);
-
-    let mut res = [0u8; 64];
-    res.copy_from_slice(&public.serialize()[1..65]);
-    let public = H160::from(H256::from_slice(Keccak256::digest(res).as_slice()));
+    let (secret, public) = hd::derive_eth_key(&mnemonic.to_seed(""), &hd::eth_path(0)).unwrap();
 
     (mnemonic, secret, public)
 }
@@ -112,6 +113,10 @@ pub struct TxMetric {
     pub hash: Option<H256>, // Tx hash
     pub status: u64,        // 1 - success, other - fail
     pub wait: u64,          // seconds for waiting tx receipt
+    pub nonce: Option<U256>,
+    pub send_time: u64,          // milliseconds since the unix epoch
+    pub block: Option<u64>,      // the block including the tx
+    pub latency_ms: Option<u64>, // milliseconds from sending to receipt
 }
 
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
@@ -122,14 +127,18 @@ pub struct TransferMetrics {
     pub txs: Vec<TxMetric>,
 }
 
+impl TransferMetrics {
+    /// write a record per tx
+    pub fn write_to(&self, writer: &MetricsWriter) -> Result<()> {
+        self.txs
+            .iter()
+            .try_for_each(|tx| writer.write(&TxRecord::from_metric(self.from, tx)))
+    }
+}
+
 #[derive(Debug)]
 pub struct TestClient {
-    pub web3: Arc<web3::Web3<Http>>,
-    pub eth: Arc<web3::api::Eth<Http>>,
-    pub accounts: Arc<web3::api::Accounts<Http>>,
-    pub root_sk: secp256k1::SecretKey,
-    pub root_addr: Address,
-    pub overflow_flag: AtomicUsize,
+    pub inner: AsyncTestClient,
     pub rt: Runtime,
 }
 
@@ -141,70 +150,49 @@ pub struct NetworkInfo {
     pub frc20_code: Option<Bytes>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct TestClientOpts {
+    /// one endpoint or comma seperated endpoints
     pub endpoint_url: Option<String>,
+    /// the root key, a hex key or a keystore, only needed to send from the root account
     pub secret_file: Option<String>,
     pub timeout: Option<u64>,
+    pub policy: EndpointPolicy,
+    /// the highest send rate, txs per second
+    pub max_rate: Option<f64>,
 }
 
 impl TestClient {
-    pub fn setup(url: Option<String>, timeout: Option<u64>) -> Self {
+    pub fn setup(url: Option<String>, timeout: Option<u64>) -> Result<Self> {
         let opts = TestClientOpts {
             endpoint_url: url,
-            secret_file: None,
             timeout,
+            ..Default::default()
         };
 
         Self::setup_with_opts(opts)
     }
 
-    pub fn setup_with_opts(opts: TestClientOpts) -> Self {
-        let TestClientOpts {
-            endpoint_url: url,
-            secret_file,
-            timeout,
-        } = opts;
-
-        let secret = secret_file.unwrap_or(".secret".to_owned());
-        let client = Client::builder()
-            .timeout(Duration::from_secs(timeout.unwrap_or(3)))
-            .build()
-            .unwrap();
-        let url = Url::parse(url.as_deref().unwrap_or(WEB3_SRV)).unwrap();
-        let transport = Http::with_client(client, url);
-        let web3 = Arc::new(web3::Web3::new(transport));
-        let eth = Arc::new(web3.eth());
-        let accounts = Arc::new(web3.accounts());
-        let (root_sk, root_addr) = extract_keypair_from_file(secret);
+    pub fn setup_with_opts(opts: TestClientOpts) -> Result<Self> {
+        let inner = AsyncTestClient::setup_with_opts(opts)?;
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .unwrap();
 
-        Self {
-            web3,
-            eth,
-            accounts,
-            root_sk,
-            root_addr,
-            rt,
-            overflow_flag: AtomicUsize::from(0),
-        }
+        Ok(Self { inner, rt })
     }
 
     pub fn chain_id(&self) -> Option<U256> {
-        self.rt.block_on(self.eth.chain_id()).ok()
+        self.rt.block_on(self.inner.chain_id())
     }
 
     pub fn block_number(&self) -> Option<U64> {
-        self.rt.block_on(self.eth.block_number()).ok()
+        self.rt.block_on(self.inner.block_number())
     }
 
     pub fn current_block(&self) -> Option<Block<H256>> {
-        self.rt
-            .block_on(self.eth.block(BlockId::Number(BlockNumber::Latest)))
-            .unwrap_or_default()
+        self.rt.block_on(self.inner.current_block())
     }
 
     pub fn block_with_tx_hashes(&self, id: BlockId) -> Option<Block<H256>> {
@@ -217,22 +205,12 @@ impl TestClient {
         interval: Option<u64>,
         times: Option<u64>,
     ) -> Option<Block<H256>> {
-        let interval = interval.unwrap_or(1);
-        let mut retries = 1u64;
-        loop {
-            if let Ok(Some(block)) = self.rt.block_on(self.eth.block(id)) {
-                break Some(block);
-            }
-            if times == Some(retries) || times == Some(0u64) {
-                break None;
-            }
-            retries += 1;
-            std::thread::sleep(Duration::from_secs(interval));
-        }
+        self.rt
+            .block_on(self.inner.block_with_tx_hashes_inner(id, interval, times))
     }
 
     pub fn nonce(&self, from: Address, block: Option<BlockNumber>) -> Option<U256> {
-        self.rt.block_on(self.eth.transaction_count(from, block)).ok()
+        self.rt.block_on(self.inner.nonce(from, block))
     }
 
     pub fn pending_nonce(&self, from: Address) -> Option<U256> {
@@ -240,101 +218,76 @@ impl TestClient {
     }
 
     pub fn pending_nonce_inner(&self, from: Address, interval: Option<u64>, times: Option<u64>) -> Option<U256> {
-        let interval = interval.unwrap_or(5);
-        let mut tries = 1u64;
-        loop {
-            match self
-                .rt
-                .block_on(self.eth.transaction_count(from, Some(BlockNumber::Pending)))
-            {
-                Ok(nonce) => break Some(nonce),
-                Err(e) => error!("failed to get nonce, tries {}, {:?}", tries, e),
-            }
-            std::thread::sleep(Duration::from_secs(interval));
-            if times == Some(tries) || times == Some(0u64) {
-                break None;
-            }
-            tries += 1;
-        }
+        self.rt.block_on(self.inner.pending_nonce_inner(from, interval, times))
     }
 
     pub fn gas_price(&self) -> Option<U256> {
-        self.rt.block_on(self.eth.gas_price()).ok()
+        self.rt.block_on(self.inner.gas_price())
     }
 
-    pub fn frc20_code(&self) -> Option<Bytes> {
+    /// the root key, it's loaded from `TestClientOpts::secret_file`
+    pub fn root(&self) -> Result<(secp256k1::SecretKey, Address)> {
+        self.inner.root()
+    }
+
+    /// a new nonce manager sharing endpoints with this client
+    pub fn nonce_manager(&self) -> NonceManager {
+        NonceManager::new(self.inner.clone())
+    }
+
+    /// follow the block height, by `newHeads` if there's a WebSocket endpoint
+    pub fn heads(&self) -> Result<HeadWatcher> {
+        HeadWatcher::start(self.inner.pool.clone())
+    }
+
+    /// fetch and decode the logs of `query`, and keep following new blocks if `follow`
+    pub fn watch_logs<F>(
+        &self,
+        abi: Option<&web3::ethabi::Contract>,
+        query: &LogQuery,
+        follow: bool,
+        f: F,
+    ) -> Result<u64>
+    where
+        F: FnMut(DecodedLog) -> Result<()>,
+    {
+        let heads = follow.then(|| self.heads()).transpose()?;
         self.rt
-            .block_on(self.eth.code(H160::from_low_u64_be(FRC20_ADDRESS), None))
-            .ok()
+            .block_on(logs::watch_logs(&self.inner, abi, query, heads.as_ref(), f))
+    }
+
+    pub fn frc20_code(&self) -> Option<Bytes> {
+        self.rt.block_on(self.inner.frc20_code())
     }
 
     #[allow(unused)]
     pub fn transaction(&self, id: TransactionId) -> Option<Transaction> {
-        self.rt.block_on(self.eth.transaction(id)).unwrap_or_default()
+        self.rt.block_on(self.inner.transaction(id))
     }
 
     pub fn transaction_receipt(&self, hash: H256) -> Option<TransactionReceipt> {
-        self.rt.block_on(self.eth.transaction_receipt(hash)).unwrap_or_default()
+        self.rt.block_on(self.inner.transaction_receipt(hash))
     }
 
     #[allow(unused)]
     pub fn accounts(&self) -> Vec<Address> {
-        self.rt.block_on(self.eth.accounts()).unwrap_or_default()
+        self.rt.block_on(self.inner.node_accounts())
     }
 
     pub fn balance(&self, address: Address, number: Option<BlockNumber>) -> U256 {
-        self.rt.block_on(self.eth.balance(address, number)).unwrap_or_default()
+        self.rt.block_on(self.inner.balance(address, number))
     }
 
     pub fn wait_for_tx_receipt(&self, hash: H256, interval: Duration, times: u64) -> (u64, Option<TransactionReceipt>) {
-        let mut wait = 0;
-        let mut retry = times;
-        loop {
-            if let Some(receipt) = self.transaction_receipt(hash) {
-                wait = times + 1 - retry;
-                break (wait, Some(receipt));
-            } else {
-                std::thread::sleep(interval);
-                retry -= 1;
-                if retry == 0 {
-                    break (wait, None);
-                }
-            }
-        }
+        self.rt.block_on(self.inner.wait_for_tx_receipt(hash, interval, times))
     }
 
-    pub fn check_wait_overflow(&self, id: usize, interval: Option<u64>) {
-        loop {
-            let flag = self.overflow_flag.load(Ordering::Relaxed);
-            if flag == 0 || flag == id {
-                break;
-            }
-            std::thread::sleep(Duration::from_secs(interval.unwrap_or(3)));
-        }
+    pub fn parse_error(&self, err: Option<&(dyn StdError + 'static)>) -> Error {
+        self.inner.parse_error(err)
     }
 
-    pub fn parse_error(&self, err: Option<&dyn StdError>) -> Error {
-        match err {
-            Some(e) => {
-                let err_str = e.to_string();
-                if err_str.contains("broadcast_tx_sync") {
-                    Error::SyncTx
-                } else if err_str.contains("Transaction check error") {
-                    Error::CheckTx
-                } else if err_str.contains("error sending request") {
-                    Error::SendErr
-                } else if err_str.contains("InternalError") {
-                    if err_str.contains("InvalidNonce") {
-                        Error::TxInternalErr(InternalError::InvalidNonce(err_str))
-                    } else {
-                        Error::TxInternalErr(InternalError::Other(err_str))
-                    }
-                } else {
-                    Error::Other(err_str)
-                }
-            }
-            None => Error::Other("empty error".to_string()),
-        }
+    pub fn decode_error(&self, err: &web3::Error) -> Error {
+        self.inner.decode_error(err)
     }
 
     pub fn distribution(
@@ -345,16 +298,22 @@ impl TestClient {
         block_time: &Option<u64>,
         need_wait: bool,
         need_retry: bool,
+        fee: &TxFee,
     ) -> Result<TransferMetrics> {
         let mut results = vec![];
         let mut succeed = 0u64;
         let total = targets.len();
-        let source_address = source.unwrap_or((self.root_sk, self.root_addr)).1;
-        let source_sk = source.unwrap_or((self.root_sk, self.root_addr)).0;
+        let (source_sk, source_address) = match source {
+            Some(source) => source,
+            None => self.root()?,
+        };
         let wait_time = block_time.unwrap_or(BLOCK_TIME) * 3 + 1;
         let chain_id = self.chain_id().map(|id| id.as_u64());
-        let gas_price = self.gas_price();
-        let nonce = RefCell::new(self.pending_nonce(source_address).unwrap());
+        let mut fee = fee.clone();
+        if fee.gas_price.is_none() && fee.oracle.is_none() {
+            fee.gas_price = self.gas_price();
+        }
+        let nonces = self.nonce_manager();
         let last_err_cnt = RefCell::new(0u64);
         targets
             .iter()
@@ -370,8 +329,6 @@ impl TestClient {
                     to,
                     value: *am,
                     chain_id,
-                    gas_price,
-                    nonce: Some(*nonce.borrow()),
                     ..Default::default()
                 };
                 (tp, tm)
@@ -379,39 +336,38 @@ impl TestClient {
             .enumerate()
             // Sign the txs (can be done offline)
             .for_each(|(idx, (mut tx_object, mut metric))| {
+                let nonce = self.rt.block_on(nonces.next(source_address)).unwrap();
+                tx_object.nonce = Some(nonce);
+                match self.rt.block_on(self.inner.resolve_fee(&fee)) {
+                    Ok(fee) => fee.apply(&mut tx_object),
+                    Err(e) => {
+                        warn!("{:?}, fallback to the node gas price", e);
+                        fee.apply(&mut tx_object)
+                    }
+                }
                 match self
                     .rt
-                    .block_on(self.accounts.sign_transaction(tx_object.clone(), &source_sk))
+                    .block_on(self.inner.sign_transaction(tx_object.clone(), &source_sk))
                 {
                     Ok(signed) => {
-                        self.check_wait_overflow(id, None);
-                        match self.rt.block_on(self.eth.send_raw_transaction(signed.raw_transaction)) {
+                        self.inner.rate.acquire_blocking();
+                        metric.nonce = Some(nonce);
+                        metric.send_time = now_millis();
+                        match self.rt.block_on(
+                            self.inner
+                                .send_raw_transaction(Some(source_address), signed.raw_transaction),
+                        ) {
                             Ok(hash) => {
                                 metric.hash = Some(hash);
                                 debug!("{}/{} {:?} {:?}", idx + 1, total, metric.to, hash);
-                                nonce.borrow_mut().add_assign(U256::one());
-                                if let Ok(val) =
-                                    self.overflow_flag
-                                        .compare_exchange(id, 0, Ordering::Acquire, Ordering::Relaxed)
-                                {
-                                    warn!("overflow flag cleared by {} me {}", val, id);
-                                }
+                                self.inner.rate.on_accepted();
                             }
                             Err(e) => {
-                                match self.parse_error(e.source()) {
-                                    Error::SyncTx => {
-                                        if let Ok(mut val) = self.overflow_flag.compare_exchange(
-                                            0,
-                                            id,
-                                            Ordering::Acquire,
-                                            Ordering::Relaxed,
-                                        ) {
-                                            if val == 0 {
-                                                val = id;
-                                            }
-                                            warn!("overflow flag set by {}, me {}", val, id);
-                                        }
-                                        self.check_wait_overflow(id, None);
+                                let err = self.decode_error(&e);
+                                match &err {
+                                    Error::MempoolFull(_) => {
+                                        warn!("mempool full, worker {}", id);
+                                        self.inner.rate.on_rejected();
                                     }
                                     Error::SendErr => {
                                         // TODO: adjust timeout
@@ -424,63 +380,33 @@ impl TestClient {
                                         error!("Internal error: {:?}", e);
                                     }
                                     _ => {
-                                        error!("other error {:?}", e);
-                                    }
-                                }
-                                let mut skip = false;
-                                while self.overflow_flag.load(Ordering::Relaxed) == id {
-                                    warn!("try to check if error persists {}", id);
-                                    let mut tx_object = tx_object.clone();
-                                    if let Some(nonce) = self.pending_nonce(source_address) {
-                                        tx_object.nonce = Some(nonce);
-                                    }
-                                    if let Ok(signed) =
-                                        self.rt.block_on(self.accounts.sign_transaction(tx_object, &source_sk))
-                                    {
-                                        match self
-                                            .rt
-                                            .block_on(self.eth.send_raw_transaction(signed.raw_transaction.clone()))
-                                        {
-                                            Ok(_) => {
-                                                if self.overflow_flag.compare_exchange(
-                                                    id,
-                                                    0,
-                                                    Ordering::Acquire,
-                                                    Ordering::Relaxed,
-                                                ) != Ok(id)
-                                                {
-                                                    panic!("This should never happened");
-                                                } else {
-                                                    skip = true;
-                                                    break;
-                                                }
-                                            }
-                                            Err(e) => {
-                                                error!("Failed to send tx {:?}, continue to trying", e);
-                                            }
-                                        }
-                                    } else {
-                                        error!("Failed to sign tx, nothing we can do...")
+                                        error!("{} error {}", err.kind(), err);
                                     }
-                                    std::thread::sleep(Duration::from_secs(3));
                                 }
-                                if need_retry && !skip {
+                                let _ = self.rt.block_on(nonces.on_error(source_address, nonce, &err));
+                                if need_retry {
                                     error!("retry for error {:?}", e);
                                     metric.status = 97;
                                     let wait_time = 2u64;
                                     last_err_cnt.borrow_mut().add_assign(1);
                                     let factor = *last_err_cnt.borrow();
                                     std::thread::sleep(Duration::from_secs(wait_time * factor));
-                                    // retrieve nonce if failed to send tx
-                                    *nonce.borrow_mut() = self.pending_nonce(source_address).unwrap();
                                     // give it another chance
-                                    tx_object.nonce = Some(*nonce.borrow());
+                                    let nonce = self.rt.block_on(nonces.next(source_address)).unwrap();
+                                    tx_object.nonce = Some(nonce);
                                     if let Ok(signed) =
-                                        self.rt.block_on(self.accounts.sign_transaction(tx_object, &source_sk))
+                                        self.rt.block_on(self.inner.sign_transaction(tx_object, &source_sk))
                                     {
-                                        match self.rt.block_on(self.eth.send_raw_transaction(signed.raw_transaction)) {
+                                        self.inner.rate.acquire_blocking();
+                                        metric.nonce = Some(nonce);
+                                        metric.send_time = now_millis();
+                                        match self.rt.block_on(
+                                            self.inner
+                                                .send_raw_transaction(Some(source_address), signed.raw_transaction),
+                                        ) {
                                             Ok(hash) => {
                                                 metric.hash = Some(hash);
+                                                self.inner.rate.on_accepted();
                                                 warn!(
                                                     "retry {}/{} {:?} {:?} {}",
                                                     idx + 1,
@@ -490,7 +416,6 @@ impl TestClient {
                                                     *last_err_cnt.borrow()
                                                 );
                                                 *last_err_cnt.borrow_mut() = 0;
-                                                nonce.borrow_mut().add_assign(U256::one());
                                             }
                                             Err(e) => {
                                                 error!(
@@ -502,7 +427,11 @@ impl TestClient {
                                                     e
                                                 );
                                                 last_err_cnt.borrow_mut().add_assign(1);
-                                                *nonce.borrow_mut() = self.pending_nonce(source_address).unwrap();
+                                                let err = self.decode_error(&e);
+                                                if let Error::MempoolFull(_) = err {
+                                                    self.inner.rate.on_rejected();
+                                                }
+                                                let _ = self.rt.block_on(nonces.on_error(source_address, nonce, &err));
                                             }
                                         }
                                     } else {
@@ -514,7 +443,8 @@ impl TestClient {
                                             *last_err_cnt.borrow(),
                                             e
                                         );
-                                        *nonce.borrow_mut() = self.pending_nonce(source_address).unwrap();
+                                        let err = Error::Other("sign error".to_string());
+                                        let _ = self.rt.block_on(nonces.on_error(source_address, nonce, &err));
                                     }
                                 }
                             }
@@ -523,8 +453,9 @@ impl TestClient {
                     Err(e) => {
                         error!("give up sign {}/{} {:?} {:?}", idx + 1, total, metric.to, e);
                         metric.status = 98;
-                        // retrieve nonce if failed to send tx
-                        *nonce.borrow_mut() = self.pending_nonce(source_address).unwrap();
+                        // the nonce is not used if failed to sign tx
+                        let err = Error::Other(e.to_string());
+                        let _ = self.rt.block_on(nonces.on_error(source_address, nonce, &err));
                     }
                 }
 
@@ -544,24 +475,24 @@ impl TestClient {
 
         results.iter_mut().enumerate().for_each(|(idx, metric)| {
             let mut retry = wait_time;
-            loop {
-                if let Some(hash) = metric.hash {
-                    if let Some(receipt) = self.transaction_receipt(hash) {
-                        if let Some(status) = receipt.status {
-                            if status == U64::from(1u64) {
-                                succeed += 1;
-                                metric.status = 1;
-                            }
+            while let Some(hash) = metric.hash {
+                if let Some(receipt) = self.transaction_receipt(hash) {
+                    if let Some(status) = receipt.status {
+                        if status == U64::from(1u64) {
+                            succeed += 1;
+                            metric.status = 1;
                         }
-                        metric.wait = wait_time + 1 - retry;
+                    }
+                    metric.wait = wait_time + 1 - retry;
+                    metric.block = receipt.block_number.map(|n| n.as_u64());
+                    metric.latency_ms = Some(now_millis().saturating_sub(metric.send_time));
+                    break;
+                } else {
+                    std::thread::sleep(Duration::from_secs(1));
+                    retry -= 1;
+                    if retry == 0 {
+                        metric.wait = wait_time;
                         break;
-                    } else {
-                        std::thread::sleep(Duration::from_secs(1));
-                        retry -= 1;
-                        if retry == 0 {
-                            metric.wait = wait_time;
-                            break;
-                        }
                     }
                 }
             }
@@ -590,29 +521,58 @@ impl TestClient {
         source: &secp256k1::SecretKey,
         target: &(Address, U256),
         chain_id: Option<u64>,
-        gas_price: Option<U256>,
+        fee: &TxFee,
         nonce: Option<U256>,
     ) -> Result<H256> {
-        let (account, amount) = target;
-        let tx_object = TransactionParameters {
-            to: Some(*account),
-            value: *amount,
-            chain_id,
-            gas_price,
-            nonce,
-            ..Default::default()
-        };
-        // Sign the txs (can be done offline)
-        match self.rt.block_on(self.accounts.sign_transaction(tx_object, source)) {
-            Ok(signed) => {
-                let result = self.rt.block_on(self.eth.send_raw_transaction(signed.raw_transaction));
-                match result {
-                    Err(e) => Err(self.parse_error(e.source())),
-                    Ok(hash) => Ok(hash),
-                }
-            }
-            Err(e) => Err(self.parse_error(e.source())),
-        }
+        self.rt
+            .block_on(self.inner.distribution_simple(source, target, chain_id, fee, nonce))
+    }
+
+    pub fn sign_transfer_offline(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        chain_id: u64,
+        fee: &TxFee,
+        nonce: U256,
+    ) -> Result<RawTx> {
+        self.rt
+            .block_on(self.inner.sign_transfer_offline(source, target, chain_id, fee, nonce))
+    }
+
+    pub fn send_signed(&self, from: Option<Address>, raw: Bytes) -> Result<H256> {
+        self.rt.block_on(self.inner.send_signed(from, raw))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn contract_call_simple(
+        &self,
+        source: &secp256k1::SecretKey,
+        target: &(Address, U256),
+        data: &Bytes,
+        gas: U256,
+        chain_id: Option<u64>,
+        fee: &TxFee,
+        nonce: Option<U256>,
+    ) -> Result<H256> {
+        self.rt.block_on(
+            self.inner
+                .contract_call_simple(source, target, data, gas, chain_id, fee, nonce),
+        )
+    }
+
+    pub fn estimate_gas(&self, req: CallRequest) -> Result<U256> {
+        self.rt.block_on(self.inner.estimate_gas(req))
+    }
+
+    pub fn deploy_contract(
+        &self,
+        source: &secp256k1::SecretKey,
+        data: Bytes,
+        gas: Option<U256>,
+        fee: &TxFee,
+    ) -> Result<(Address, TransactionReceipt)> {
+        self.rt.block_on(self.inner.deploy_contract(source, data, gas, fee))
     }
 
     pub async fn distribute(
@@ -620,98 +580,87 @@ impl TestClient {
         source: &secp256k1::SecretKey,
         targets: &[(Address, U256)],
         delay_in_seconds: u64,
-    ) -> Result<()> {
-        for target in targets {
-            let (account, amount) = target;
-            let tx_object = TransactionParameters {
-                to: Some(*account),
-                value: *amount,
-                ..Default::default()
-            };
-            let signed = match self
-                .accounts
-                .sign_transaction(tx_object, source)
-                .map_err(|e| Error::Other(e.to_string()))
-                .await
-            {
-                Ok(s) => s,
-                Err(e) => {
-                    error!("{:?}: {:?}", account, e);
-                    continue;
-                }
-            };
-
-            let hash = match self
-                .eth
-                .send_raw_transaction(signed.raw_transaction)
-                .map_err(|e| Error::Other(e.to_string()))
-                .await
-            {
-                Ok(h) => h,
-                Err(e) => {
-                    error!("{:?}: {:?}", account, e);
-                    continue;
-                }
-            };
-
-            loop {
-                let receipt = match self
-                    .eth
-                    .transaction_receipt(hash)
-                    .map_err(|e| self.parse_error(e.source()))
-                    .await
-                {
-                    Ok(r) => r,
-                    Err(e) => {
-                        error!("{:?}: {:?}", account, e);
-                        break;
-                    }
-                };
-                match receipt {
-                    Some(receipt) => {
-                        match receipt.status {
-                            Some(r) => {
-                                info!("{account:?} {}", r == U64::one())
-                            }
-                            None => warn!("{account:?} undefined status"),
-                        }
-                        break;
-                    }
-                    None => task::yield_now().await,
-                }
-            }
-            std::thread::sleep(Duration::from_secs(delay_in_seconds));
-        }
-        Ok(())
+        fee: &TxFee,
+    ) -> Result<Vec<TxRecord>> {
+        self.inner.distribute(source, targets, delay_in_seconds, fee).await
     }
 
-    pub fn contract_deploy(&self, deploy_json: DeployJson) -> anyhow::Result<()> {
+    /// deploy the contracts of `deploy_json`, and record them in the manifest
+    pub fn contract_deploy(&self, deploy_json: DeployJson) -> anyhow::Result<ConcurrencyStats> {
+        let concurrency = deploy_json.concurrency;
         self.rt.block_on(async {
+            let (manifest_path, manifest) = load_manifest(&self.inner, deploy_json.manifest.as_deref()).await?;
+            let manifest = Arc::new(Mutex::new(manifest));
             let mut vf = Vec::new();
             for deploy_obj in deploy_json.deploy_obj {
                 let DeployJsonObj {
+                    name,
+                    builtin,
                     code_path,
                     abi_path,
                     sec_key,
                     gas,
                     gas_price,
+                    tx_type,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    access_list,
+                    gas_strategy,
                     args,
                 } = deploy_obj;
-                let args = parse_args_csv(&args)?;
-                let eth = (*self.eth.clone()).clone();
+                let (code, abi) = match builtin {
+                    Some(builtin) => (builtin.code(), builtin.abi()),
+                    None => (abi::load_code(&code_path)?, abi::load_abi(&abi_path)?),
+                };
+                let data = abi::deploy_data(&abi, code, &args)?;
+                // named after the builtin contract or the code file by default
+                let name = match (name, builtin) {
+                    (Some(name), _) => name,
+                    (None, Some(builtin)) => builtin.to_string(),
+                    (None, None) => Path::new(&code_path)
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or(code_path),
+                };
+                // the manifest may be used from another directory
+                let abi_path = match builtin {
+                    Some(_) => String::new(),
+                    None => fs::canonicalize(&abi_path)
+                        .map(|path| path.display().to_string())
+                        .unwrap_or(abi_path),
+                };
+                let client = self.inner.clone();
+                let manifest = manifest.clone();
+                let fee = json_fee(
+                    tx_type,
+                    gas_price,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    access_list,
+                    gas_strategy,
+                )?;
+                let fee = self.inner.resolve_fee(&fee).await?;
 
                 let f = move || async move {
-                    let succeed =
-                        match contract_deploy(eth, &sec_key, &code_path, &abi_path, gas, gas_price, args).await {
-                            Ok(v) => {
-                                info!("contract address: {:?}", v);
-                                true
-                            }
-                            Err(e) => {
-                                error!("deploy contract failed: {:?}", e);
-                                false
-                            }
-                        };
+                    let succeed = match contract_deploy(&client, &sec_key, data, gas, &fee).await {
+                        Ok((address, receipt)) => {
+                            info!("contract {} address: {:?}", name, address);
+                            manifest.lock().await.insert(DeployedContract {
+                                name,
+                                address,
+                                builtin,
+                                abi_path,
+                                tx_hash: receipt.transaction_hash,
+                                block: receipt.block_number.map(|n| n.as_u64()),
+                                deployer: receipt.from,
+                            });
+                            true
+                        }
+                        Err(e) => {
+                            error!("deploy contract failed: {:?}", e);
+                            false
+                        }
+                    };
                     if !succeed {
                         bail!("deploy failed");
                     }
@@ -721,60 +670,82 @@ impl TestClient {
                 vf.push(f);
             }
 
-            let (success_task, total_times) = multi_tasks_impl(vf).await?;
-
-            info!(
-                "success task: {} total times: {} average time: {}",
-                success_task,
-                total_times,
-                if success_task == 0 {
-                    0
-                } else {
-                    total_times / success_task as u128
-                }
-            );
-
-            anyhow::Ok(())
-        })?;
+            let stats = Arc::new(ConcurrencyLimiter::new(concurrency)).run(vf).await;
+            if stats.succeeded > 0 {
+                manifest.lock().await.save(&manifest_path)?;
+                info!("deployed contracts saved to {}", manifest_path.display());
+            }
 
-        Ok(())
+            anyhow::Ok(stats)
+        })
     }
 
-    pub fn contract_call(&self, call_json: CallJson) -> anyhow::Result<()> {
+    pub fn contract_call(&self, call_json: CallJson) -> anyhow::Result<ConcurrencyStats> {
+        let concurrency = call_json.concurrency;
         self.rt.block_on(async {
+            let manifest = if call_json.call_obj.iter().any(|c| c.contract.is_some()) {
+                Some(load_manifest(&self.inner, call_json.manifest.as_deref()).await?.1)
+            } else {
+                None
+            };
+            // shared by all call objects, those of the same key may run at once
+            let nonces = Arc::new(self.nonce_manager());
             let mut vf = Vec::new();
-            for call_obj in call_json.call_obj {
+            for (index, call_obj) in call_json.call_obj.into_iter().enumerate() {
                 let CallJsonObj {
+                    contract,
                     contract_addr,
+                    builtin,
                     abi_path,
                     sec_key,
                     gas,
                     gas_price,
+                    tx_type,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    access_list,
+                    gas_strategy,
                     func_name,
                     args,
+                    preflight,
+                    wait_receipt,
                 } = call_obj;
-                let args = parse_args_csv(&args)?;
-                let eth = (*self.eth.clone()).clone();
+                let (contract_addr, builtin, abi_path) = match (manifest.as_ref(), contract) {
+                    (Some(manifest), Some(name)) => {
+                        manifest_contract(manifest, &name, contract_addr, builtin, abi_path)?
+                    }
+                    _ => (contract_addr, builtin, abi_path),
+                };
+                let abi = contract_abi(builtin, &abi_path)?;
+                let function = abi::find_function(&abi, &func_name)?;
+                let args = abi::encode_json_args(&function.inputs, &args)?;
+                let data = function.encode_input(&args)?;
+                let call = CallCheck {
+                    name: format!("call {} {}", index, abi::signature(function)),
+                    preflight,
+                    wait_receipt,
+                };
+                let client = self.inner.clone();
+                let nonces = nonces.clone();
+                let fee = json_fee(
+                    tx_type,
+                    gas_price,
+                    max_fee_per_gas,
+                    max_priority_fee_per_gas,
+                    access_list,
+                    gas_strategy,
+                )?;
+                let fee = self.inner.resolve_fee(&fee).await?;
 
                 let f = move || async move {
-                    let succeed = match contract_call(
-                        eth,
-                        &sec_key,
-                        &contract_addr,
-                        &abi_path,
-                        gas,
-                        gas_price,
-                        &func_name,
-                        args,
-                    )
-                    .await
-                    {
+                    let res = contract_call(&client, &nonces, &abi, &contract_addr, &sec_key, gas, &fee, data, &call);
+                    let succeed = match res.await {
                         Ok(v) => {
-                            info!("transaction hash: {:?}", v);
+                            info!("{} transaction hash: {:?}", call.name, v);
                             true
                         }
                         Err(e) => {
-                            info!("call contract failed: {:?}", e);
+                            info!("{} failed: {:?}", call.name, e);
                             false
                         }
                     };
@@ -787,39 +758,41 @@ impl TestClient {
                 vf.push(f);
             }
 
-            let (success_task, total_times) = multi_tasks_impl(vf).await?;
+            let stats = Arc::new(ConcurrencyLimiter::new(concurrency)).run(vf).await;
 
-            info!(
-                "success task: {} total times: {} average time: {}",
-                success_task,
-                total_times,
-                if success_task == 0 {
-                    0
-                } else {
-                    total_times / success_task as u128
-                }
-            );
-
-            anyhow::Ok(())
-        })?;
-
-        Ok(())
+            anyhow::Ok(stats)
+        })
     }
 
     pub fn contract_query(&self, query_json: QueryJson) -> anyhow::Result<()> {
         self.rt.block_on(async {
             let QueryJson {
+                contract,
                 contract_addr,
+                builtin,
                 abi_path,
                 func_name,
                 args,
+                output,
+                manifest,
             } = query_json;
-            let args = parse_args_csv(&args)?;
+            let (contract_addr, builtin, abi_path) = match contract {
+                Some(name) => {
+                    let (_, manifest) = load_manifest(&self.inner, manifest.as_deref()).await?;
+                    manifest_contract(&manifest, &name, contract_addr, builtin, abi_path)?
+                }
+                None => (contract_addr, builtin, abi_path),
+            };
 
-            let eth = (*self.eth.clone()).clone();
-            let result = contract_query(eth, &contract_addr, &abi_path, &func_name, args).await?;
+            let eth = self.inner.eth();
+            let abi = contract_abi(builtin, &abi_path)?;
+            let result = contract_query(eth, &contract_addr, &abi, &func_name, &args).await?;
 
-            info!("query result: {:?}", result);
+            info!("query result: {}", result);
+            if let Some(output) = output {
+                fs::write(&output, serde_json::to_string_pretty(&result)?)?;
+                info!("query result saved to {}", output);
+            }
 
             anyhow::Ok(())
         })?;
@@ -830,40 +803,144 @@ impl TestClient {
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DeployJsonObj {
+    /// the name in the manifest, the builtin contract or the stem of `code_path` by default
+    #[serde(default)]
+    pub name: Option<String>,
+    /// a contract shipped with the binary, in place of `code_path` and `abi_path`
+    #[serde(default)]
+    pub builtin: Option<BenchContract>,
+    #[serde(default)]
     pub code_path: String,
+    #[serde(default)]
     pub abi_path: String,
     pub sec_key: String,
     pub gas: u32,
     pub gas_price: u32,
-    pub args: String,
+    #[serde(default)]
+    pub tx_type: TxType,
+    #[serde(default)]
+    pub max_fee_per_gas: Option<u64>,
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<u64>,
+    #[serde(default)]
+    pub access_list: Option<AccessList>,
+    /// 'fixed:<wei>', 'node', 'refresh:<blocks>', 'percentile:<p>[:<blocks>]', it takes precedence over `gas_price`
+    #[serde(default)]
+    pub gas_strategy: Option<String>,
+    /// a JSON array encoded by the constructor inputs of the ABI, or legacy comma separated values
+    #[serde(default)]
+    pub args: serde_json::Value,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DeployJson {
     pub deploy_obj: Vec<DeployJsonObj>,
+    /// the bounds of concurrent deployments
+    #[serde(default)]
+    pub concurrency: ConcurrencyLimits,
+    /// the manifest file of deployed contracts, `deployments.<chain_id>.json` by default
+    #[serde(default)]
+    pub manifest: Option<String>,
 }
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CallJsonObj {
+    /// a contract in the manifest, in place of `contract_addr` and `abi_path`
+    #[serde(default)]
+    pub contract: Option<String>,
+    #[serde(default)]
     pub contract_addr: String,
+    /// a contract shipped with the binary, in place of `abi_path`
+    #[serde(default)]
+    pub builtin: Option<BenchContract>,
+    #[serde(default)]
     pub abi_path: String,
     pub sec_key: String,
+    /// the gas limit, 0 to estimate it with a 20% margin
+    #[serde(default)]
     pub gas: u32,
     pub gas_price: u32,
+    #[serde(default)]
+    pub tx_type: TxType,
+    #[serde(default)]
+    pub max_fee_per_gas: Option<u64>,
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<u64>,
+    #[serde(default)]
+    pub access_list: Option<AccessList>,
+    /// 'fixed:<wei>', 'node', 'refresh:<blocks>', 'percentile:<p>[:<blocks>]', it takes precedence over `gas_price`
+    #[serde(default)]
+    pub gas_strategy: Option<String>,
+    /// a function name, or a signature such as `transfer(address,uint256)` for overloaded functions
     pub func_name: String,
-    pub args: String,
+    /// a JSON array encoded by the function inputs of the ABI, or legacy comma separated values
+    #[serde(default)]
+    pub args: serde_json::Value,
+    /// simulate by `eth_call` and estimate the gas before sending, a reverting call is not sent
+    #[serde(default)]
+    pub preflight: bool,
+    /// wait for the receipt, the revert reason of a failed call is fetched by replaying it
+    #[serde(default)]
+    pub wait_receipt: bool,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct CallJson {
     pub call_obj: Vec<CallJsonObj>,
+    /// the bounds of concurrent calls
+    #[serde(default)]
+    pub concurrency: ConcurrencyLimits,
+    /// the manifest file of deployed contracts, `deployments.<chain_id>.json` by default
+    #[serde(default)]
+    pub manifest: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct QueryJson {
+    /// a contract in the manifest, in place of `contract_addr` and `abi_path`
+    #[serde(default)]
+    pub contract: Option<String>,
+    #[serde(default)]
     pub contract_addr: String,
+    /// a contract shipped with the binary, in place of `abi_path`
+    #[serde(default)]
+    pub builtin: Option<BenchContract>,
+    #[serde(default)]
     pub abi_path: String,
+    /// a function name, or a signature such as `balanceOf(address)` for overloaded functions
     pub func_name: String,
-    pub args: String,
+    /// a JSON array encoded by the function inputs of the ABI, or legacy comma separated values
+    #[serde(default)]
+    pub args: serde_json::Value,
+    /// the file to save the decoded result in json
+    #[serde(default)]
+    pub output: Option<String>,
+    /// the manifest file of deployed contracts, `deployments.<chain_id>.json` by default
+    #[serde(default)]
+    pub manifest: Option<String>,
+}
+
+fn json_fee(
+    tx_type: TxType,
+    gas_price: u32,
+    max_fee_per_gas: Option<u64>,
+    max_priority_fee_per_gas: Option<u64>,
+    access_list: Option<AccessList>,
+    gas_strategy: Option<String>,
+) -> anyhow::Result<TxFee> {
+    let oracle = match gas_strategy {
+        Some(strategy) => Some(Arc::new(GasOracle::new(
+            strategy.parse::<GasStrategy>().map_err(|e| anyhow!(e))?,
+        ))),
+        None => None,
+    };
+    Ok(TxFee {
+        tx_type,
+        gas_price: Some(gas_price.into()),
+        max_fee_per_gas: max_fee_per_gas.map(U256::from),
+        max_priority_fee_per_gas: max_priority_fee_per_gas.map(U256::from),
+        access_list,
+        oracle,
+    })
 }
 
 pub fn parse_deploy_json(pat: &PathBuf) -> anyhow::Result<DeployJson> {
@@ -887,248 +964,189 @@ pub fn parse_query_json(pat: &PathBuf) -> anyhow::Result<QueryJson> {
     Ok(query_json_obj)
 }
 
-fn parse_args_csv(args: &str) -> anyhow::Result<Vec<Token>> {
-    let mut res: Vec<Token> = Vec::new();
-
-    let args_str = args.to_string();
-    let mut csv_reader1 = csv::Reader::from_reader(args_str.as_bytes());
-
-    if let Ok(args) = csv_reader1.headers() {
-        for arg in args {
-            if arg.is_empty() {
-                bail!("arg format error!!!");
-            } else if let Ok(arg_bool) = arg.parse::<bool>() {
-                res.push(arg_bool.into_token());
-            } else if let Ok(arg_int) = arg.parse::<Int>() {
-                res.push(arg_int.into_token());
-            } else if let Ok(arg_uint) = arg.parse::<Uint>() {
-                res.push(arg_uint.into_token());
-            } else if let Ok(arg_address) = arg.parse::<Address>() {
-                res.push(arg_address.into_token());
-            } else if let Ok(arg_h160) = arg.parse::<H160>() {
-                res.push(arg_h160.into_token());
-            } else if let Ok(arg_h256) = arg.parse::<H256>() {
-                res.push(arg_h256.into_token());
-            } else if let Ok(arg_u128) = arg.parse::<U128>() {
-                res.push(arg_u128.into_token());
-            } else if let Ok(arg_u256) = arg.parse::<U256>() {
-                res.push(arg_u256.into_token());
-            } else {
-                let arg_string = arg.to_string();
-                res.push(arg_string.into_token());
-            }
-        }
+// the manifest of the connected chain, and its path
+async fn load_manifest(client: &AsyncTestClient, path: Option<&str>) -> anyhow::Result<(PathBuf, Manifest)> {
+    let chain_id = client
+        .chain_id()
+        .await
+        .ok_or_else(|| anyhow!("failed to get the chain id"))?
+        .as_u64();
+    let path = path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Manifest::default_path(chain_id));
+    let manifest = Manifest::load(&path, chain_id)?;
+    Ok((path, manifest))
+}
+
+// the address, builtin and ABI path of the contract `name`, given ones take precedence over the manifest
+fn manifest_contract(
+    manifest: &Manifest,
+    name: &str,
+    contract_addr: String,
+    builtin: Option<BenchContract>,
+    abi_path: String,
+) -> anyhow::Result<(String, Option<BenchContract>, String)> {
+    let deployed = manifest.get(name)?;
+    let contract_addr = if contract_addr.is_empty() {
+        format!("{:?}", deployed.address)
+    } else {
+        contract_addr
+    };
+    if builtin.is_none() && abi_path.is_empty() {
+        Ok((contract_addr, deployed.builtin, deployed.abi_path.clone()))
+    } else {
+        Ok((contract_addr, builtin, abi_path))
     }
+}
 
-    Ok(res)
+// the ABI of a builtin contract, or of the file `abi_path`
+fn contract_abi(builtin: Option<BenchContract>, abi_path: &str) -> anyhow::Result<web3::ethabi::Contract> {
+    match builtin {
+        Some(builtin) => Ok(builtin.abi()),
+        None => abi::load_abi(abi_path),
+    }
 }
 
 async fn contract_deploy(
-    eth: Eth<Http>,
+    client: &AsyncTestClient,
     sec_key: &str,
-    code_path: &str,
-    abi_path: &str,
+    data: Vec<u8>,
     gas: u32,
-    gas_price: u32,
-    args: Vec<Token>,
-) -> web3::contract::Result<H160> {
-    let byetcode = fs::read(code_path).unwrap();
-    let abi = fs::read(abi_path).unwrap();
-
-    let secretkey = SecretKey2::from_str(sec_key).unwrap();
-
-    let contract = if args.is_empty() {
-        Contract::deploy(eth, &abi)?
-            .confirmations(1)
-            .poll_interval(time::Duration::from_millis(PULL_INTERVAL))
-            .options(Options::with(|opt| {
-                opt.gas = Some(gas.into());
-                opt.gas_price = Some(gas_price.into());
-                // opt.nonce = Some(nonce + nonce_add);
-            }))
-            .sign_with_key_and_execute(std::str::from_utf8(&byetcode).unwrap(), (), &secretkey, None)
-            .await?
-    } else {
-        Contract::deploy(eth, &abi)?
-            .confirmations(1)
-            .poll_interval(time::Duration::from_millis(PULL_INTERVAL))
-            .options(Options::with(|opt| {
-                opt.gas = Some(gas.into());
-                opt.gas_price = Some(gas_price.into());
-                // opt.nonce = Some(nonce + nonce_add);
-            }))
-            .sign_with_key_and_execute(std::str::from_utf8(&byetcode).unwrap(), args, &secretkey, None)
-            .await?
-    };
+    fee: &TxFee,
+) -> Result<(Address, TransactionReceipt)> {
+    let secretkey = SecretKey2::from_str(sec_key).map_err(|e| Error::Other(e.to_string()))?;
+    client
+        .deploy_contract(&secretkey, Bytes(data), Some(gas.into()), fee)
+        .await
+}
 
-    Ok(contract.address())
+// the checks around a call object, `name` identifies it in logs
+struct CallCheck {
+    name: String,
+    preflight: bool,
+    wait_receipt: bool,
 }
 
+// the reason of a revert, decoded by the custom errors of `abi`
+fn revert_reason(outcome: &CallOutcome, abi: &web3::ethabi::Contract) -> Option<String> {
+    match outcome {
+        CallOutcome::Success(_) => None,
+        CallOutcome::Reverted { data, message } if data.is_empty() => Some(message.clone()),
+        CallOutcome::Reverted { data, .. } => Some(abi::decode_revert(data, Some(abi))),
+    }
+}
+
+// `data` is the encoded function call, `Contract::signed_call` can't select overloaded functions
 #[allow(clippy::too_many_arguments)]
 async fn contract_call(
-    eth: Eth<Http>,
+    client: &AsyncTestClient,
+    nonces: &NonceManager,
+    abi: &web3::ethabi::Contract,
     contr_addr: &str,
     sec_key: &str,
-    // _account: &str,
-    abi_path: &str,
     gas: u32,
-    gas_price: u32,
-    func_name: &str,
-    args: Vec<Token>,
-) -> web3::contract::Result<H256> {
-    let abi = fs::read(abi_path).unwrap();
-    let contr_addr: H160 = contr_addr.parse().unwrap();
-    // let _account: H160 = _account.parse().unwrap();
-    let contract = Contract::from_json(eth, contr_addr, &abi)?;
-    let secretkey = SecretKey2::from_str(sec_key).unwrap();
-
-    let opt = Options {
-        gas: Some(gas.into()),
-        gas_price: Some(gas_price.into()),
+    fee: &TxFee,
+    data: Vec<u8>,
+    call: &CallCheck,
+) -> anyhow::Result<H256> {
+    let contr_addr: H160 = contr_addr.parse()?;
+    let secretkey = SecretKey2::from_str(sec_key)?;
+    let from = SecretKeyRef::new(&secretkey).address();
+    let req = CallRequest {
+        from: Some(from),
+        to: Some(contr_addr),
+        data: Some(Bytes(data.clone())),
         ..Default::default()
     };
 
-    let transaction_hash = if args.is_empty() {
-        contract.signed_call(func_name, (), opt, &secretkey).await?
+    if call.preflight {
+        if let Some(reason) = revert_reason(&client.simulate_call(req.clone(), None).await?, abi) {
+            bail!("reverted in preflight: {}", reason);
+        }
+    }
+    let estimate = if call.preflight || gas == 0 {
+        let estimate = client.estimate_gas(req).await?;
+        info!("{} estimated gas {}", call.name, estimate);
+        Some(estimate)
     } else {
-        contract.signed_call(func_name, args, opt, &secretkey).await?
+        None
+    };
+    let gas = match estimate {
+        Some(estimate) if gas == 0 => estimate * 6 / 5,
+        Some(estimate) if estimate > U256::from(gas) => {
+            warn!("{} gas limit {} is below the estimate {}", call.name, gas, estimate);
+            gas.into()
+        }
+        _ => gas.into(),
+    };
+
+    let nonce = nonces.next(from).await?;
+    let mut tx = TransactionParameters {
+        nonce: Some(nonce),
+        to: Some(contr_addr),
+        gas,
+        data: Bytes(data),
+        ..Default::default()
     };
+    fee.apply(&mut tx);
+    let sent = match client.sign_transaction(tx, &secretkey).await {
+        Ok(signed) => client.send_signed(Some(from), signed.raw_transaction).await,
+        Err(e) => Err(client.decode_error(&e)),
+    };
+    let transaction_hash = match sent {
+        Ok(hash) => hash,
+        Err(e) => {
+            let _ = nonces.on_error(from, nonce, &e).await;
+            return Err(e.into());
+        }
+    };
+    if !call.wait_receipt {
+        return Ok(transaction_hash);
+    }
 
+    let (_, receipt) = client
+        .wait_for_tx_receipt(transaction_hash, Duration::from_secs(1), BLOCK_TIME * 10)
+        .await;
+    let receipt = receipt.ok_or_else(|| anyhow!("no receipt of {:?}", transaction_hash))?;
+    if receipt.status != Some(U64::one()) {
+        let reason = match client.replay_transaction(transaction_hash).await {
+            Ok(outcome) => revert_reason(&outcome, abi).unwrap_or_else(|| "no revert in replay".to_string()),
+            Err(e) => format!("replay failed: {e}"),
+        };
+        bail!(
+            "{:?} reverted in block {:?}: {}",
+            transaction_hash,
+            receipt.block_number,
+            reason
+        );
+    }
+    info!(
+        "{} included in block {:?}, gas used {:?}",
+        call.name, receipt.block_number, receipt.gas_used
+    );
     Ok(transaction_hash)
 }
 
 async fn contract_query(
-    eth: Eth<Http>,
+    eth: Eth<Transport>,
     contr_addr: &str,
     // _account: &str,
-    abi_path: &str,
+    abi: &web3::ethabi::Contract,
     func_name: &str,
-    args: Vec<Token>,
-) -> web3::contract::Result<U256> {
-    let abi = fs::read(abi_path).unwrap();
-    let contr_addr: H160 = contr_addr.parse().unwrap();
+    args: &serde_json::Value,
+) -> anyhow::Result<serde_json::Value> {
+    let contr_addr: H160 = contr_addr.parse()?;
     // let _account: H160 = _account.parse().unwrap();
 
-    let contract = Contract::from_json(eth, contr_addr, &abi)?;
-    // let _secretkey = SecretKey::from_str(_sec_key).unwrap();
-    let opt = Options::default();
-
-    let result = if args.is_empty() {
-        contract.query(func_name, (), None, opt, None).await?
-    } else {
-        contract.query(func_name, args, None, opt, None).await?
+    let function = abi::find_function(abi, func_name)?;
+    let args = abi::encode_json_args(&function.inputs, args)?;
+    let req = CallRequest {
+        to: Some(contr_addr),
+        data: Some(Bytes(function.encode_input(&args)?)),
+        ..Default::default()
     };
+    let data = eth.call(req, None).await?;
+    let tokens = function.decode_output(&data.0)?;
 
-    Ok(result)
-}
-
-async fn multi_tasks_impl<F, T>(vf: Vec<F>) -> anyhow::Result<(u32, u128)>
-where
-    F: FnOnce() -> T,
-    T: Future<Output = anyhow::Result<()>> + Send + 'static,
-{
-    let mut task_queue = Vec::with_capacity(vf.len());
-    for f in vf {
-        let af = f();
-        let task = tokio::spawn(async move {
-            CUR_TASKS.store(CUR_TASKS.load(Ordering::Acquire) + 1, Ordering::Release);
-
-            let beg_time = get_timestamp();
-
-            if af.await.is_ok() {
-                let end_time = get_timestamp();
-                update_res_queue_secs(end_time - beg_time).await;
-            }
-            CUR_TASKS.store(CUR_TASKS.load(Ordering::Acquire) - 1, Ordering::Release);
-        });
-        task_queue.push(task);
-
-        while MAX_TASKS.load(Ordering::Acquire) <= CUR_TASKS.load(Ordering::Acquire) {
-            let task = task_queue.pop().unwrap();
-            task.await?;
-        }
-    }
-
-    let (tx1, rx1) = tokio::sync::mpsc::channel(2);
-    tokio::spawn(max_tasks_update(rx1));
-
-    for task in task_queue {
-        task.await?;
-    }
-
-    tx1.send(()).await?;
-
-    // error occur
-    // return anyhow::Ok((RES_QUEUE_SECS.lock().await.0, RES_QUEUE_SECS.lock().await.1));
-
-    let success_task = RES_QUEUE_SECS.lock().await.0;
-    let total_times = RES_QUEUE_SECS.lock().await.1;
-
-    anyhow::Ok((success_task, total_times))
-}
-
-async fn max_tasks_update(mut rx: Receiver<()>) {
-    loop {
-        let res_queue_secs = RES_QUEUE_SECS.lock().await;
-        let average_time_queue = &res_queue_secs.2;
-        if average_time_queue.len() > 1 {
-            let end_cost_time = average_time_queue.iter().last().unwrap();
-            let mut big: u8 = 0;
-            let mut less: u8 = 0;
-
-            for cost in average_time_queue.iter().rev().skip(1) {
-                if end_cost_time > cost && (end_cost_time - *cost) > DELTA_RANGE {
-                    big += 1;
-                }
-                if end_cost_time < cost && (*cost - end_cost_time) > DELTA_RANGE {
-                    less += 1;
-                }
-            }
-
-            match big.cmp(&less) {
-                std::cmp::Ordering::Greater => {
-                    MAX_TASKS.store(2 * MAX_TASKS.load(Ordering::Acquire), Ordering::Release);
-                }
-                std::cmp::Ordering::Less => {
-                    MAX_TASKS.store(MAX_TASKS.load(Ordering::Acquire) - 1, Ordering::Release);
-                }
-                std::cmp::Ordering::Equal => {
-                    let end_cost_time2 = average_time_queue.iter().rev().skip(1).rev().last().unwrap();
-
-                    if end_cost_time > end_cost_time2 && end_cost_time - end_cost_time2 > DELTA_RANGE {
-                        MAX_TASKS.store(2 * MAX_TASKS.load(Ordering::Acquire), Ordering::Release);
-                    } else if end_cost_time < end_cost_time2 && end_cost_time2 - end_cost_time > DELTA_RANGE {
-                        MAX_TASKS.store(MAX_TASKS.load(Ordering::Acquire) - 1, Ordering::Release);
-                    }
-                }
-            }
-        }
-
-        tokio::time::sleep(tokio::time::Duration::from_millis(UPDATE_INTERVAL)).await;
-        if rx.try_recv().is_ok() {
-            break;
-        }
-    }
-}
-
-async fn update_res_queue_secs(interval: u128) {
-    let mut res_queue_secs = RES_QUEUE_SECS.lock().await;
-
-    res_queue_secs.0 += 1;
-    res_queue_secs.1 += interval;
-    let aveage_time = res_queue_secs.1 / res_queue_secs.0 as u128;
-    res_queue_secs.2.push(aveage_time);
-
-    while res_queue_secs.2.len() > RES_QUEUE_MAX_LEN {
-        res_queue_secs.2.pop();
-    }
-}
-
-fn get_timestamp() -> u128 {
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    match SystemTime::now().duration_since(UNIX_EPOCH) {
-        Ok(n) => n.as_millis(),
-        Err(_) => panic!("SystemTime before UNIX EPOCH!"),
-    }
+    Ok(abi::outputs_to_json(&function.outputs, &tokens))
 }
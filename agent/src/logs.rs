@@ -0,0 +1,138 @@
+use crate::{
+    abi,
+    client::AsyncTestClient,
+    error::{Error, Result},
+    HeadWatcher,
+};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use web3::{
+    ethabi::{Contract, RawLog},
+    types::{Address, Log, H256, U256},
+};
+
+/// The blocks of an `eth_getLogs` request by default, nodes limit the range of a query
+pub const DEFAULT_LOG_BATCH: u64 = 1000;
+
+/// The logs of a contract over a range of blocks
+#[derive(Debug, Clone)]
+pub struct LogQuery {
+    pub address: Address,
+    /// the first block, the latest by default
+    pub from_block: Option<u64>,
+    /// the last block, the latest by default, or no end in follow mode
+    pub to_block: Option<u64>,
+    /// the blocks of a request
+    pub batch: u64,
+}
+
+/// A log decoded by the events of its contract ABI
+#[derive(Debug, Clone, Serialize)]
+pub struct DecodedLog {
+    pub block: Option<u64>,
+    pub tx_hash: Option<H256>,
+    pub log_index: Option<U256>,
+    pub address: Address,
+    /// the event name, none if no event of the ABI matches
+    pub event: Option<String>,
+    /// the event parameters by name, or the raw topics and data if not decoded
+    pub params: Value,
+}
+
+/// Decode a log by the events of `abi`, anonymous events can't be matched
+pub fn decode_log(abi: Option<&Contract>, log: &Log) -> DecodedLog {
+    let decoded = abi.zip(log.topics.first()).and_then(|(abi, topic)| {
+        abi.events()
+            .filter(|e| !e.anonymous && e.signature() == *topic)
+            .find_map(|e| {
+                let raw = RawLog {
+                    topics: log.topics.clone(),
+                    data: log.data.0.clone(),
+                };
+                e.parse_log(raw).ok().map(|parsed| (e.name.clone(), parsed))
+            })
+    });
+    let (event, params) = match decoded {
+        Some((name, parsed)) => {
+            let params = parsed
+                .params
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    // unnamed parameters are named by position
+                    let name = if p.name.is_empty() {
+                        i.to_string()
+                    } else {
+                        p.name.clone()
+                    };
+                    (name, abi::token_to_json(&p.value))
+                })
+                .collect::<Map<_, _>>();
+            (Some(name), Value::Object(params))
+        }
+        None => {
+            let params = json!({
+                "topics": log.topics,
+                "data": format!("0x{}", hex::encode(&log.data.0)),
+            });
+            (None, params)
+        }
+    };
+    DecodedLog {
+        block: log.block_number.map(|n| n.as_u64()),
+        tx_hash: log.transaction_hash,
+        log_index: log.log_index,
+        address: log.address,
+        event,
+        params,
+    }
+}
+
+/// Fetch the logs of `query` in order of blocks, `f` is called with each decoded log.
+///
+/// With `heads` it follows new blocks after the range, until `to_block` if any.
+/// Returns the number of logs.
+pub async fn watch_logs<F>(
+    client: &AsyncTestClient,
+    abi: Option<&Contract>,
+    query: &LogQuery,
+    heads: Option<&HeadWatcher>,
+    mut f: F,
+) -> Result<u64>
+where
+    F: FnMut(DecodedLog) -> Result<()>,
+{
+    let latest = client
+        .block_number()
+        .await
+        .ok_or_else(|| Error::Other("failed to get the block number".to_string()))?
+        .as_u64();
+    let batch = query.batch.max(1);
+    let mut from = query.from_block.unwrap_or(latest);
+    let end = match heads {
+        Some(_) => query.to_block.unwrap_or(u64::MAX),
+        None => query.to_block.unwrap_or(latest),
+    };
+    let mut head = latest.min(end);
+    let mut count = 0;
+    loop {
+        while from <= head {
+            let to = from.saturating_add(batch - 1).min(head);
+            for log in client.logs(query.address, from, to).await? {
+                f(decode_log(abi, &log))?;
+                count += 1;
+            }
+            from = to + 1;
+        }
+        match heads {
+            Some(heads) if from <= end => {
+                let height = heads.wait_for(from).await;
+                if height < from {
+                    break Err(Error::Other("stopped following new blocks".to_string()));
+                }
+                head = height.min(end);
+            }
+            _ => break Ok(count),
+        }
+    }
+}
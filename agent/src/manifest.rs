@@ -0,0 +1,80 @@
+use crate::{
+    error::{Error, Result},
+    BenchContract,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use web3::types::{Address, H256};
+
+/// A contract recorded by a deploy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployedContract {
+    pub name: String,
+    pub address: Address,
+    /// the builtin contract deployed, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builtin: Option<BenchContract>,
+    /// the ABI file, empty for builtin contracts
+    #[serde(default)]
+    pub abi_path: String,
+    pub tx_hash: H256,
+    pub block: Option<u64>,
+    pub deployer: Address,
+}
+
+/// Contracts deployed to a network by name, a redeployed name points to the latest one
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub chain_id: u64,
+    pub contracts: BTreeMap<String, DeployedContract>,
+}
+
+impl Manifest {
+    /// the manifest file of a chain, in the working directory
+    pub fn default_path(chain_id: u64) -> PathBuf {
+        PathBuf::from(format!("deployments.{chain_id}.json"))
+    }
+
+    /// load the manifest of `chain_id`, it's empty if the file doesn't exist
+    pub fn load<P: AsRef<Path>>(path: P, chain_id: u64) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self {
+                chain_id,
+                ..Default::default()
+            });
+        }
+        let data = std::fs::read_to_string(path)?;
+        let manifest: Self = serde_json::from_str(&data).map_err(|e| Error::Other(e.to_string()))?;
+        if manifest.chain_id != chain_id {
+            return Err(Error::Other(format!(
+                "{} is the manifest of chain {}, not {}",
+                path.display(),
+                manifest.chain_id,
+                chain_id
+            )));
+        }
+        Ok(manifest)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let data = serde_json::to_string_pretty(self).map_err(|e| Error::Other(e.to_string()))?;
+        std::fs::write(path, data).map_err(Error::from)
+    }
+
+    pub fn insert(&mut self, contract: DeployedContract) {
+        self.contracts.insert(contract.name.clone(), contract);
+    }
+
+    pub fn get(&self, name: &str) -> Result<&DeployedContract> {
+        self.contracts.get(name).ok_or_else(|| {
+            Error::Other(format!(
+                "contract {name} not found in the manifest of chain {}",
+                self.chain_id
+            ))
+        })
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{
+    error::{Error, Result},
+    TxMetric,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use web3::types::{Address, H256, U256};
+
+/// File formats of tx records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetricsFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl MetricsFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+impl std::fmt::Display for MetricsFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+impl std::str::FromStr for MetricsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "csv" => Ok(Self::Csv),
+            "jsonl" | "json" => Ok(Self::Jsonl),
+            n => Err(format!("invalid metrics format {n}")),
+        }
+    }
+}
+
+/// milliseconds since the unix epoch
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// One record per sent tx
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TxRecord {
+    pub source: Address,
+    pub target: Address,
+    pub nonce: Option<U256>,
+    pub hash: Option<H256>,
+    /// milliseconds since the unix epoch
+    pub send_time: u64,
+    /// the block including the tx
+    pub block: Option<u64>,
+    /// receipt status, 1 - success, 0 - failure
+    pub status: Option<u64>,
+    /// milliseconds from sending to inclusion
+    pub latency_ms: Option<u64>,
+    /// gas used by the included tx
+    pub gas_used: Option<u64>,
+    /// why the tx was not accepted
+    pub error: Option<String>,
+}
+
+impl TxRecord {
+    pub fn from_metric(source: Address, metric: &TxMetric) -> Self {
+        Self {
+            source,
+            target: metric.to,
+            nonce: metric.nonce,
+            hash: metric.hash,
+            send_time: metric.send_time,
+            block: metric.block,
+            status: metric.block.map(|_| (metric.status == 1) as u64),
+            latency_ms: metric.latency_ms,
+            gas_used: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Sink {
+    Csv(csv::Writer<File>),
+    Jsonl(BufWriter<File>),
+}
+
+/// Write tx records to a file, it's shared by all senders of a run
+#[derive(Debug)]
+pub struct MetricsWriter {
+    path: PathBuf,
+    sink: Mutex<Sink>,
+}
+
+impl MetricsWriter {
+    /// create `metric.<name>.<format>` in `dir`
+    pub fn create<P: AsRef<Path>>(dir: P, name: &str, format: MetricsFormat) -> Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(format!("metric.{}.{}", name, format.extension()));
+        let file = File::create(&path)?;
+        let sink = match format {
+            MetricsFormat::Csv => Sink::Csv(csv::Writer::from_writer(file)),
+            MetricsFormat::Jsonl => Sink::Jsonl(BufWriter::new(file)),
+        };
+        Ok(Self {
+            path,
+            sink: Mutex::new(sink),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self, record: &TxRecord) -> Result<()> {
+        let mut sink = self.sink.lock().unwrap();
+        match &mut *sink {
+            Sink::Csv(w) => w.serialize(record).map_err(|e| Error::Other(e.to_string())),
+            Sink::Jsonl(w) => {
+                serde_json::to_writer(&mut *w, record).map_err(|e| Error::Other(e.to_string()))?;
+                w.write_all(b"\n").map_err(Error::from)
+            }
+        }
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        let mut sink = self.sink.lock().unwrap();
+        match &mut *sink {
+            Sink::Csv(w) => w.flush(),
+            Sink::Jsonl(w) => w.flush(),
+        }
+        .map_err(Error::from)
+    }
+}
+
+impl Drop for MetricsWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
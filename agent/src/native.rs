@@ -1,20 +1,46 @@
 pub use prism::*;
 pub use utils::*;
 
+use finutils::{
+    ledger::{
+        data_model::{AssetRules, Operation, Transaction, BLACK_HOLE_PUBKEY_STAKING},
+        staking::{td_addr_to_bytes, PartialUnDelegation},
+    },
+    zei::{setup::PublicParams, xfr::asset_record::AssetRecordType},
+};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+use tendermint::config::PrivValidatorKey;
+use tokio::runtime::Runtime;
+
+// how long an issuance is waited for before it's transferred
+const ISSUANCE_TIMEOUT: Duration = Duration::from_secs(60);
+// the interval of looking up an issuance
+const ISSUANCE_POLL: Duration = Duration::from_secs(1);
+
 mod utils {
     pub(crate) use crate::{Error, Result};
     use finutils::ledger;
     pub(super) use finutils::{common::utils, fp_utils, wallet, zei};
     use fp_utils::ecdsa::SecpPair;
-    pub use ledger::{data_model::TX_FEE_MIN, staking::FRA};
+    pub use ledger::{
+        data_model::{AssetTypeCode, TX_FEE_MIN},
+        staking::FRA,
+    };
     use std::{path::Path, str::FromStr};
     use tendermint::block::Height;
     use tendermint_rpc::{endpoint::abci_query::AbciQuery, Client, HttpClient};
     use tokio::runtime::Runtime;
     pub(super) use utils::{gen_transfer_op_xx, new_tx_builder_to, send_tx_to};
     pub use wallet::{public_key_from_base64, restore_keypair_from_mnemonic_default};
+    use wallet::{restore_keypair_from_mnemonic_bip44, BipPath};
     pub use zei::xfr::sig::{XfrKeyPair, XfrPublicKey};
 
+    /// BIP-44 coin type of FRA
+    pub const FRA_COIN_TYPE: u32 = 917;
+
     /// fra 24, en
     pub fn generate_mnemonic(count: usize, wordslen: u8, lang: &str) -> Result<Vec<String>> {
         let mut mns = Vec::with_capacity(count);
@@ -33,15 +59,21 @@ mod utils {
     where
         P: AsRef<Path>,
     {
-        let m = std::fs::read_to_string(mn_path).map_err(Error::Io)?;
+        let m = crate::keystore::read_to_string(mn_path)?;
         restore_keypair_from_mnemonic_default(m.trim()).map_err(|o| Error::Other(o.to_string()))
     }
 
+    /// restore the FRA key at `m/44'/917'/0'/0/<index>` of a mnemonic
+    pub fn restore_fra_keypair_at(phrase: &str, index: u32) -> Result<XfrKeyPair> {
+        let path = BipPath::new(FRA_COIN_TYPE, 0, 0, index);
+        restore_keypair_from_mnemonic_bip44(phrase, "en", &path).map_err(|o| Error::Other(o.to_string()))
+    }
+
     pub fn restore_eth_keypair<P>(mn_path: P) -> Result<SecpPair>
     where
         P: AsRef<Path>,
     {
-        let eth_mn = std::fs::read_to_string(mn_path)?;
+        let eth_mn = crate::keystore::read_to_string(mn_path)?;
         let kp = SecpPair::from_phrase(eth_mn.trim(), None)
             .map_err(|o| Error::Prism(o.to_string()))?
             .0;
@@ -52,6 +84,45 @@ mod utils {
         public_key_from_base64(pk).map_err(|o| Error::Prism(o.to_string()))
     }
 
+    /// parse an asset type code in base64
+    pub fn parse_asset_code(code: &str) -> Result<AssetTypeCode> {
+        AssetTypeCode::new_from_base64(code.trim()).map_err(|o| Error::Native(o.to_string()))
+    }
+
+    /// The decimals of FRA
+    pub const FRA_DECIMALS: u8 = 6;
+
+    /// An asset moved between utxo and evm accounts
+    #[derive(Debug, Clone, Copy)]
+    pub struct AssetSpec {
+        pub code: AssetTypeCode,
+        /// the decimals of the asset definition
+        pub decimal: u8,
+        /// the max units of the asset definition, 0 for no limit
+        pub max_supply: u64,
+    }
+
+    impl AssetSpec {
+        pub fn parse(code: &str, decimal: u8, max_supply: u64) -> Result<Self> {
+            Ok(Self {
+                code: parse_asset_code(code)?,
+                decimal,
+                max_supply,
+            })
+        }
+    }
+
+    /// the tendermint rpc client of `endpoint`
+    pub fn tm_client(endpoint: &str) -> Result<HttpClient> {
+        HttpClient::new(format!("{endpoint}:26657").as_str()).map_err(|o| Error::Native(o.to_string()))
+    }
+
+    /// the height of the block including the tx of the tendermint hash `hash`, none if not found
+    pub async fn tx_height(tm_client: &HttpClient, hash: &str) -> Option<u64> {
+        let hash = hash.parse().ok()?;
+        tm_client.tx(hash, false).await.ok().map(|res| res.height.value())
+    }
+
     pub(super) fn one_shot_abci_query(
         tm_client: &HttpClient,
         path: &str,
@@ -81,7 +152,7 @@ mod utils {
 }
 
 mod prism {
-    use super::utils;
+    use super::utils::{self, AssetSpec, AssetTypeCode, FRA_DECIMALS};
     use crate::{Error, Result};
     use finutils::{fp_types, fp_utils, ledger, zei};
 
@@ -120,14 +191,21 @@ mod prism {
         }
     }
 
-    pub fn deposit(endpoint: &str, src_kp: XfrKeyPair, target_addr: H160, amount: u64) -> Result<()> {
+    /// Deposit `amount` of `asset` to the evm account `target_addr`, FRA if `asset` is none
+    pub fn deposit(
+        endpoint: &str,
+        src_kp: XfrKeyPair,
+        target_addr: H160,
+        amount: u64,
+        asset: Option<AssetTypeCode>,
+    ) -> Result<()> {
         let mut builder = utils::new_tx_builder_to(Some(endpoint)).map_err(|o| Error::Prism(o.to_string()))?;
 
         let transfer_op = utils::gen_transfer_op_xx(
             Some(endpoint),
             &src_kp,
             vec![(&BLACK_HOLE_PUBKEY_STAKING, amount)],
-            None,
+            asset,
             true,
             false,
             false,
@@ -139,7 +217,7 @@ mod prism {
 
         builder
             .add_operation(transfer_op)
-            .add_operation_convert_account(&src_kp, target_address, amount, None, None)
+            .add_operation_convert_account(&src_kp, target_address, amount, asset, None)
             .map_err(|o| Error::Prism(o.to_string()))?
             .sign(&src_kp);
 
@@ -149,13 +227,29 @@ mod prism {
         utils::send_tx_to(&tx, Some(endpoint)).map_err(|o| Error::Prism(o.to_string()))
     }
 
-    pub fn withdraw(endpoint: &str, src_kp: SecpPair, target_pk: XfrPublicKey, amount: u64) -> Result<()> {
-        let output = NonConfidentialOutput {
-            target: target_pk,
-            amount,
-            asset: ASSET_TYPE_FRA,
-            decimal: 6,
-            max_supply: 0,
+    /// Withdraw `amount` of `asset` to the utxo key `target_pk`, FRA if `asset` is none
+    pub fn withdraw(
+        endpoint: &str,
+        src_kp: SecpPair,
+        target_pk: XfrPublicKey,
+        amount: u64,
+        asset: Option<&AssetSpec>,
+    ) -> Result<()> {
+        let output = match asset {
+            Some(spec) => NonConfidentialOutput {
+                target: target_pk,
+                amount,
+                asset: spec.code.val,
+                decimal: spec.decimal,
+                max_supply: spec.max_supply,
+            },
+            None => NonConfidentialOutput {
+                target: target_pk,
+                amount,
+                asset: ASSET_TYPE_FRA,
+                decimal: FRA_DECIMALS,
+                max_supply: 0,
+            },
         };
 
         let signer = Address::from(src_kp.address());
@@ -220,7 +314,11 @@ mod prism {
 pub enum NativeOp {
     Transfer,
     Delegate,
+    Undelegate,
+    Claim,
     Stake,
+    DefineAsset,
+    IssueAsset,
 }
 
 impl std::str::FromStr for NativeOp {
@@ -230,7 +328,11 @@ impl std::str::FromStr for NativeOp {
         match s.to_lowercase().trim() {
             "transfer" => Ok(NativeOp::Transfer),
             "delegate" => Ok(NativeOp::Delegate),
+            "undelegate" => Ok(NativeOp::Undelegate),
+            "claim" => Ok(NativeOp::Claim),
             "stake" => Ok(NativeOp::Stake),
+            "define-asset" => Ok(NativeOp::DefineAsset),
+            "issue-asset" => Ok(NativeOp::IssueAsset),
             _ => Err("Unknown NativeOp".to_string()),
         }
     }
@@ -241,8 +343,29 @@ pub fn transfer(endpoint: &str, src_kp: XfrKeyPair, target_addr: XfrPublicKey, a
 }
 
 pub fn transfer_batch(endpoint: &str, src_kp: XfrKeyPair, target_list: Vec<(&XfrPublicKey, u64)>) -> Result<()> {
+    transfer_asset_batch(endpoint, src_kp, target_list, None)
+}
+
+/// Transfer `amount` of the asset `code` to `target_addr`, the fee is paid in FRA
+pub fn transfer_asset(
+    endpoint: &str,
+    src_kp: XfrKeyPair,
+    target_addr: XfrPublicKey,
+    amount: u64,
+    code: AssetTypeCode,
+) -> Result<()> {
+    transfer_asset_batch(endpoint, src_kp, vec![(&target_addr, amount)], Some(code))
+}
+
+/// Transfer the asset `code` to all targets in a tx, FRA if `code` is none
+pub fn transfer_asset_batch(
+    endpoint: &str,
+    src_kp: XfrKeyPair,
+    target_list: Vec<(&XfrPublicKey, u64)>,
+    code: Option<AssetTypeCode>,
+) -> Result<()> {
     let mut builder = new_tx_builder_to(Some(endpoint)).map_err(|o| Error::Native(o.to_string()))?;
-    let op = gen_transfer_op_xx(Some(endpoint), &src_kp, target_list, None, true, false, false, None)
+    let op = gen_transfer_op_xx(Some(endpoint), &src_kp, target_list, code, true, false, false, None)
         .map_err(|o| Error::Native(o.to_string()))?;
 
     builder.add_operation(op);
@@ -252,3 +375,192 @@ pub fn transfer_batch(endpoint: &str, src_kp: XfrKeyPair, target_list: Vec<(&Xfr
 
     send_tx_to(&tx, Some(endpoint)).map_err(|o| Error::Native(o.to_string()))
 }
+
+// a transfer of `amount` FRA to the staking black hole, the principal of a delegation
+fn staking_principal_op(endpoint: &str, src_kp: &XfrKeyPair, amount: u64) -> Result<Operation> {
+    gen_transfer_op_xx(
+        Some(endpoint),
+        src_kp,
+        vec![(&BLACK_HOLE_PUBKEY_STAKING, amount)],
+        None,
+        true,
+        false,
+        false,
+        Some(AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType),
+    )
+    .map_err(|o| Error::Native(o.to_string()))
+}
+
+// an operation paying the tx fee only
+fn fee_op(endpoint: &str, src_kp: &XfrKeyPair) -> Result<Operation> {
+    gen_transfer_op_xx(Some(endpoint), src_kp, vec![], None, true, false, false, None)
+        .map_err(|o| Error::Native(o.to_string()))
+}
+
+// sign `tx` and send it, returns the tendermint hash of the tx
+fn sign_and_send(endpoint: &str, mut tx: Transaction, src_kp: &XfrKeyPair) -> Result<String> {
+    tx.sign_to_map(src_kp);
+    send_tx_to(&tx, Some(endpoint)).map_err(|o| Error::Native(o.to_string()))?;
+    Ok(hex::encode_upper(tx.hash_tm_rawbytes()))
+}
+
+/// Delegate `amount` FRA to the validator of the tendermint address `validator`, returns the tx hash
+pub fn delegate(endpoint: &str, src_kp: XfrKeyPair, validator: &str, amount: u64) -> Result<String> {
+    let mut builder = new_tx_builder_to(Some(endpoint)).map_err(|o| Error::Native(o.to_string()))?;
+    let principal_op = staking_principal_op(endpoint, &src_kp, amount)?;
+
+    builder
+        .add_operation(principal_op)
+        .add_operation_delegation(&src_kp, amount, validator.to_owned());
+
+    sign_and_send(endpoint, builder.take_transaction(), &src_kp)
+}
+
+/// Undelegate `amount` FRA from `validator`, or all delegations if `partial` is none, returns the tx hash
+pub fn undelegate(endpoint: &str, src_kp: XfrKeyPair, partial: Option<(&str, u64)>) -> Result<String> {
+    let mut builder = new_tx_builder_to(Some(endpoint)).map_err(|o| Error::Native(o.to_string()))?;
+    let fee_op = fee_op(endpoint, &src_kp)?;
+
+    let partial = match partial {
+        Some((validator, amount)) => {
+            let validator = td_addr_to_bytes(validator).map_err(|o| Error::Native(o.to_string()))?;
+            // the undelegated amount is held by a new delegator id until it's unbonded
+            let new_delegator = restore_keypair_from_mnemonic_default(&gen_one_mnemonic_default()?)
+                .map_err(|o| Error::Native(o.to_string()))?
+                .get_pk();
+            Some(PartialUnDelegation::new(amount, new_delegator, validator))
+        }
+        None => None,
+    };
+    builder
+        .add_operation(fee_op)
+        .add_operation_undelegation(&src_kp, partial);
+
+    sign_and_send(endpoint, builder.take_transaction(), &src_kp)
+}
+
+/// Claim `amount` FRA of the rewards from `validator`, or all rewards if `amount` is none, returns the tx hash
+pub fn claim(endpoint: &str, src_kp: XfrKeyPair, validator: &str, amount: Option<u64>) -> Result<String> {
+    let mut builder = new_tx_builder_to(Some(endpoint)).map_err(|o| Error::Native(o.to_string()))?;
+    let fee_op = fee_op(endpoint, &src_kp)?;
+    let validator = td_addr_to_bytes(validator).map_err(|o| Error::Native(o.to_string()))?;
+
+    builder
+        .add_operation(fee_op)
+        .add_operation_claim(Some(validator.to_vec()), &src_kp, amount);
+
+    sign_and_send(endpoint, builder.take_transaction(), &src_kp)
+}
+
+/// Stake `amount` FRA to become a validator, returns the tx hash.
+///
+/// `validator_key` is the `priv_validator_key.json` of the tendermint node,
+/// `commission_rate` is a fraction in `[0, 1]`.
+pub fn stake<P: AsRef<Path>>(
+    endpoint: &str,
+    src_kp: XfrKeyPair,
+    validator_key: P,
+    amount: u64,
+    commission_rate: f64,
+    memo: Option<String>,
+) -> Result<String> {
+    if !(0.0..=1.0).contains(&commission_rate) {
+        return Err(Error::Native(format!(
+            "commission rate {commission_rate} is not within [0, 1]"
+        )));
+    }
+    let rate = [(commission_rate * 10000.0) as u64, 10000];
+    let validator_key =
+        PrivValidatorKey::load_json_file(&validator_key.as_ref()).map_err(|o| Error::Native(o.to_string()))?;
+
+    let mut builder = new_tx_builder_to(Some(endpoint)).map_err(|o| Error::Native(o.to_string()))?;
+    builder
+        .add_operation_staking(
+            &src_kp,
+            amount,
+            &validator_key.priv_key,
+            validator_key.pub_key.to_bytes(),
+            rate,
+            memo,
+        )
+        .map_err(|o| Error::Native(o.to_string()))?;
+    let principal_op = staking_principal_op(endpoint, &src_kp, amount)?;
+    builder.add_operation(principal_op);
+
+    sign_and_send(endpoint, builder.take_transaction(), &src_kp)
+}
+
+/// Define an asset issued by `src_kp`, returns the asset code and the tx hash.
+///
+/// A random code is generated if `code` is none, `max_supply` is unlimited if none.
+pub fn define_asset(
+    endpoint: &str,
+    src_kp: XfrKeyPair,
+    code: Option<AssetTypeCode>,
+    decimal: u8,
+    max_supply: Option<u64>,
+    memo: &str,
+) -> Result<(AssetTypeCode, String)> {
+    let code = code.unwrap_or_else(AssetTypeCode::gen_random);
+    let mut rules = AssetRules::default();
+    rules
+        .set_decimals(decimal)
+        .map_err(|o| Error::Native(o.to_string()))?
+        .set_max_units(max_supply);
+
+    let mut builder = new_tx_builder_to(Some(endpoint)).map_err(|o| Error::Native(o.to_string()))?;
+    let fee_op = fee_op(endpoint, &src_kp)?;
+    builder
+        .add_operation_create_asset(&src_kp, Some(code), rules, memo)
+        .map_err(|o| Error::Native(o.to_string()))?
+        .add_operation(fee_op);
+
+    let hash = sign_and_send(endpoint, builder.take_transaction(), &src_kp)?;
+    Ok((code, hash))
+}
+
+/// Issue `amount` of the asset `code` to its issuer `src_kp`, returns the tx hash.
+///
+/// Only the issuer owns a new issuance, `issue_asset_to` moves it to another key.
+pub fn issue_asset_to_self(endpoint: &str, src_kp: XfrKeyPair, code: &AssetTypeCode, amount: u64) -> Result<String> {
+    let mut builder = new_tx_builder_to(Some(endpoint)).map_err(|o| Error::Native(o.to_string()))?;
+    let fee_op = fee_op(endpoint, &src_kp)?;
+    let seq_id = builder.get_seq_id();
+    builder
+        .add_basic_issue_asset(
+            &src_kp,
+            code,
+            seq_id,
+            amount,
+            AssetRecordType::NonConfidentialAmount_NonConfidentialAssetType,
+            &PublicParams::default(),
+        )
+        .map_err(|o| Error::Native(o.to_string()))?
+        .add_operation(fee_op);
+
+    sign_and_send(endpoint, builder.take_transaction(), &src_kp)
+}
+
+/// Issue `amount` of the asset `code` and transfer it to `target`, returns the tx hash of the issuance.
+///
+/// The issued utxo can't be spent until it's committed, so the transfer is another tx.
+pub fn issue_asset_to(
+    endpoint: &str,
+    src_kp: XfrKeyPair,
+    target: XfrPublicKey,
+    code: &AssetTypeCode,
+    amount: u64,
+) -> Result<String> {
+    let hash = issue_asset_to_self(endpoint, src_kp.clone(), code, amount)?;
+    let tm_client = tm_client(endpoint)?;
+    let rt = Runtime::new().map_err(|o| Error::Native(o.to_string()))?;
+    let deadline = Instant::now() + ISSUANCE_TIMEOUT;
+    while rt.block_on(tx_height(&tm_client, &hash)).is_none() {
+        if Instant::now() >= deadline {
+            return Err(Error::Timeout(format!("issuance {hash} is not committed")));
+        }
+        std::thread::sleep(ISSUANCE_POLL);
+    }
+    transfer_asset(endpoint, src_kp, target, amount, *code)?;
+    Ok(hash)
+}
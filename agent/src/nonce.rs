@@ -0,0 +1,262 @@
+use crate::{
+    client::AsyncTestClient,
+    error::{Error, Result},
+};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+use web3::types::{Address, U256};
+
+#[derive(Debug, Default)]
+struct AccountNonce {
+    // the next nonce never allocated
+    next: U256,
+    // allocated nonces whose txs were not accepted, they'll be allocated again first
+    holes: BTreeSet<U256>,
+}
+
+impl AccountNonce {
+    fn new(next: U256) -> Self {
+        Self {
+            next,
+            ..Default::default()
+        }
+    }
+
+    // the lowest hole first, or the next nonce
+    fn allocate(&mut self) -> U256 {
+        match self.holes.iter().next().copied() {
+            Some(nonce) => {
+                self.holes.remove(&nonce);
+                nonce
+            }
+            None => {
+                let nonce = self.next;
+                self.next = nonce + 1;
+                nonce
+            }
+        }
+    }
+
+    // `nonce` is not used by any tx, it's allocated again
+    fn give_back(&mut self, nonce: U256) {
+        if nonce + 1 == self.next {
+            self.next = nonce;
+        } else if nonce < self.next {
+            self.holes.insert(nonce);
+        }
+    }
+
+    fn gap(&self, address: Address, pending: U256) -> Option<NonceGap> {
+        if pending == self.next && self.holes.is_empty() {
+            None
+        } else {
+            Some(NonceGap {
+                address,
+                local: self.next,
+                pending,
+                holes: self.holes.iter().copied().collect(),
+            })
+        }
+    }
+}
+
+// the tx was rejected before it reached the mempool, its nonce is not consumed.
+// After timeouts and transport errors the tx may be in the mempool already.
+fn rejected_before_admission(err: &Error) -> bool {
+    matches!(err, Error::InsufficientFunds(_) | Error::CheckTx | Error::Rpc(_))
+}
+
+/// A mismatch between local nonces and the pending nonce of the node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonceGap {
+    pub address: Address,
+    pub local: U256,
+    pub pending: U256,
+    pub holes: Vec<U256>,
+}
+
+impl std::fmt::Display for NonceGap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} local {} pending {} holes {:?}",
+            self.address, self.local, self.pending, self.holes
+        )
+    }
+}
+
+/// Keep nonces of source accounts locally.
+///
+/// Nonces are fetched from the node at the first use of an account, and
+/// re-synchronized when a failed tx may have consumed its nonce.
+#[derive(Debug)]
+pub struct NonceManager {
+    client: AsyncTestClient,
+    accounts: std::sync::Mutex<HashMap<Address, Arc<Mutex<Option<AccountNonce>>>>>,
+}
+
+impl NonceManager {
+    pub fn new(client: AsyncTestClient) -> Self {
+        Self {
+            client,
+            accounts: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn account(&self, address: Address) -> Arc<Mutex<Option<AccountNonce>>> {
+        self.accounts.lock().unwrap().entry(address).or_default().clone()
+    }
+
+    async fn pending(&self, address: Address) -> Result<U256> {
+        self.client
+            .pending_nonce(address)
+            .await
+            .ok_or_else(|| Error::Other(format!("failed to get pending nonce of {address:?}")))
+    }
+
+    /// allocate a nonce for a new tx of `address`
+    pub async fn next(&self, address: Address) -> Result<U256> {
+        let account = self.account(address);
+        let mut account = account.lock().await;
+        if account.is_none() {
+            *account = Some(AccountNonce::new(self.pending(address).await?));
+        }
+        Ok(account.as_mut().unwrap().allocate())
+    }
+
+    /// re-synchronize the nonce of `address` with the node, all local holes are dropped
+    pub async fn resync(&self, address: Address) -> Result<U256> {
+        let account = self.account(address);
+        let mut account = account.lock().await;
+        let pending = self.pending(address).await?;
+        if let Some(old) = account.as_ref() {
+            debug!("nonce of {:?} resynced {} -> {}", address, old.next, pending);
+        }
+        *account = Some(AccountNonce::new(pending));
+        Ok(pending)
+    }
+
+    /// the tx of `address` with `nonce` was not accepted because of `err`.
+    ///
+    /// The nonce is given back only if the tx is known to be rejected before admission,
+    /// otherwise the nonce is resynced with the node.
+    pub async fn on_error(&self, address: Address, nonce: U256, err: &Error) -> Result<()> {
+        if rejected_before_admission(err) {
+            let account = self.account(address);
+            let mut account = account.lock().await;
+            if let Some(account) = account.as_mut() {
+                account.give_back(nonce);
+            }
+            Ok(())
+        } else {
+            warn!("resync nonce of {:?} for {}", address, err);
+            self.resync(address).await.map(|_| ())
+        }
+    }
+
+    /// allocated nonces of `address` which are not used by any accepted tx
+    pub async fn holes(&self, address: Address) -> Vec<U256> {
+        let account = self.account(address);
+        let account = account.lock().await;
+        account
+            .as_ref()
+            .map(|a| a.holes.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// compare local nonces with the pending nonce of the node, `None` if they are consistent
+    pub async fn detect_gap(&self, address: Address) -> Result<Option<NonceGap>> {
+        let account = self.account(address);
+        let account = account.lock().await;
+        match account.as_ref() {
+            Some(a) => Ok(a.gap(address, self.pending(address).await?)),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocate(account: &mut AccountNonce, n: usize) -> Vec<u64> {
+        (0..n).map(|_| account.allocate().as_u64()).collect()
+    }
+
+    #[test]
+    fn consecutive() {
+        let mut account = AccountNonce::new(5.into());
+        assert_eq!(allocate(&mut account, 3), vec![5, 6, 7]);
+        assert_eq!(account.next, 8.into());
+    }
+
+    #[test]
+    fn give_back_the_last() {
+        let mut account = AccountNonce::new(0.into());
+        allocate(&mut account, 3);
+        account.give_back(2.into());
+        assert_eq!(account.next, 2.into());
+        assert!(account.holes.is_empty());
+        assert_eq!(allocate(&mut account, 2), vec![2, 3]);
+    }
+
+    #[test]
+    fn holes_first() {
+        let mut account = AccountNonce::new(0.into());
+        allocate(&mut account, 5);
+        account.give_back(3.into());
+        account.give_back(1.into());
+        assert_eq!(account.holes.iter().map(|n| n.as_u64()).collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(allocate(&mut account, 3), vec![1, 3, 5]);
+        assert!(account.holes.is_empty());
+    }
+
+    #[test]
+    fn never_allocated() {
+        let mut account = AccountNonce::new(3.into());
+        account.give_back(7.into());
+        assert_eq!(account.next, 3.into());
+        assert!(account.holes.is_empty());
+    }
+
+    #[test]
+    fn gaps() {
+        let address = Address::repeat_byte(1);
+        let mut account = AccountNonce::new(0.into());
+        allocate(&mut account, 3);
+        assert_eq!(account.gap(address, 3.into()), None);
+
+        let gap = account.gap(address, 2.into()).unwrap();
+        assert_eq!((gap.local, gap.pending), (3.into(), 2.into()));
+
+        account.give_back(1.into());
+        let gap = account.gap(address, 3.into()).unwrap();
+        assert_eq!(gap.holes, vec![U256::from(1)]);
+    }
+
+    #[test]
+    fn give_back_only_before_admission() {
+        let rpc = |code, message: &str| Error::from_rpc(code, message.to_string(), None);
+        assert!(rejected_before_admission(&rpc(
+            -32000,
+            "insufficient funds for gas * price + value"
+        )));
+        assert!(rejected_before_admission(&Error::CheckTx));
+        assert!(rejected_before_admission(&rpc(-32602, "invalid params")));
+
+        assert!(!rejected_before_admission(&rpc(-32000, "nonce too low")));
+        assert!(!rejected_before_admission(&rpc(
+            -32000,
+            "replacement transaction underpriced"
+        )));
+        assert!(!rejected_before_admission(&rpc(-32005, "txpool is full")));
+        assert!(!rejected_before_admission(&Error::Timeout(
+            "request timed out".to_string()
+        )));
+        assert!(!rejected_before_admission(&Error::SendErr));
+    }
+}
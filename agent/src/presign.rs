@@ -0,0 +1,43 @@
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+use web3::types::{Address, Bytes, H256, U256};
+
+/// A transfer signed ahead of sending, it's replayed by `send_raw_transaction` only
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RawTx {
+    pub source: Address,
+    pub target: Address,
+    pub nonce: U256,
+    pub hash: H256,
+    /// the signed RLP-encoded tx
+    pub raw: Bytes,
+}
+
+/// write signed txs to `path`, one json object per line
+pub fn write_raw_txs<P: AsRef<Path>>(path: P, txs: &[RawTx]) -> Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    for tx in txs {
+        serde_json::to_writer(&mut w, tx).map_err(|e| Error::Other(e.to_string()))?;
+        w.write_all(b"\n")?;
+    }
+    w.flush().map_err(Error::from)
+}
+
+/// read signed txs written by `write_raw_txs`, in the same order
+pub fn read_raw_txs<P: AsRef<Path>>(path: P) -> Result<Vec<RawTx>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut txs = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        txs.push(serde_json::from_str(&line).map_err(|e| Error::Other(e.to_string()))?);
+    }
+    Ok(txs)
+}
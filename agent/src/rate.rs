@@ -0,0 +1,202 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+/// The default highest send rate, txs per second
+pub const DEFAULT_MAX_RATE: f64 = 10_000.0;
+/// The default lowest send rate, txs per second
+pub const DEFAULT_MIN_RATE: f64 = 1.0;
+
+// the rate is cut at most once in this interval, a burst of rejections is one congestion signal
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(1);
+// the rate is multiplied by this factor on congestion
+const DECREASE_FACTOR: f64 = 0.5;
+// the rate grows by this fraction of the max rate once a full second of txs is accepted
+const INCREASE_RATIO: f64 = 0.01;
+
+#[derive(Debug)]
+struct RateState {
+    // current rate, txs per second
+    rate: f64,
+    // tokens left in the bucket, negative if senders are queued
+    tokens: f64,
+    last_refill: Instant,
+    last_decrease: Option<Instant>,
+    // accepted txs since the last rate change
+    accepted: u64,
+}
+
+/// A send rate shared by all senders, adjusted by AIMD.
+///
+/// Senders take a token per tx from a bucket refilled at the current rate.
+/// The rate is halved when the mempool rejects txs and slowly raised again while txs are accepted.
+#[derive(Debug)]
+pub struct RateController {
+    min_rate: f64,
+    max_rate: f64,
+    state: Mutex<RateState>,
+}
+
+impl Default for RateController {
+    fn default() -> Self {
+        Self::new(DEFAULT_MIN_RATE, DEFAULT_MAX_RATE)
+    }
+}
+
+impl RateController {
+    /// a controller starting at `max_rate`
+    pub fn new(min_rate: f64, max_rate: f64) -> Self {
+        let min_rate = min_rate.max(f64::MIN_POSITIVE);
+        let max_rate = max_rate.max(min_rate);
+        Self {
+            min_rate,
+            max_rate,
+            state: Mutex::new(RateState {
+                rate: max_rate,
+                tokens: max_rate,
+                last_refill: Instant::now(),
+                last_decrease: None,
+                accepted: 0,
+            }),
+        }
+    }
+
+    /// current rate, txs per second
+    pub fn rate(&self) -> f64 {
+        self.state.lock().unwrap().rate
+    }
+
+    // take a token, returns how long the caller should wait for it
+    fn reserve(&self) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        // at most one second of burst
+        state.tokens = (state.tokens + elapsed * state.rate).min(state.rate);
+        state.last_refill = now;
+        state.tokens -= 1.0;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / state.rate)
+        }
+    }
+
+    /// wait for the turn of the next tx
+    pub async fn acquire(&self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// wait for the turn of the next tx, blocking the current thread
+    pub fn acquire_blocking(&self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// a tx was accepted, raise the rate additively once a full second of txs went through
+    pub fn on_accepted(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.accepted += 1;
+        if state.rate >= self.max_rate || (state.accepted as f64) < state.rate {
+            return;
+        }
+        let old = state.rate;
+        state.rate = (old + self.max_rate * INCREASE_RATIO).min(self.max_rate);
+        state.accepted = 0;
+        info!("send rate raised {:.1} -> {:.1} tx/s", old, state.rate);
+    }
+
+    /// the mempool rejected a tx, cut the rate multiplicatively
+    pub fn on_rejected(&self) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        if matches!(state.last_decrease, Some(t) if now.duration_since(t) < DECREASE_COOLDOWN) {
+            return;
+        }
+        let old = state.rate;
+        state.rate = (old * DECREASE_FACTOR).max(self.min_rate);
+        state.tokens = state.tokens.min(0.0);
+        state.last_decrease = Some(now);
+        state.accepted = 0;
+        info!(
+            "send rate cut {:.1} -> {:.1} tx/s for mempool rejection",
+            old, state.rate
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // as if the last cut was long ago
+    fn cool_down(rate: &RateController) {
+        let mut state = rate.state.lock().unwrap();
+        state.last_decrease = state.last_decrease.map(|t| t - DECREASE_COOLDOWN * 2);
+    }
+
+    #[test]
+    fn halved_once_per_cooldown() {
+        let rate = RateController::new(1.0, 100.0);
+        assert_eq!(rate.rate(), 100.0);
+        rate.on_rejected();
+        assert_eq!(rate.rate(), 50.0);
+        // the same congestion
+        rate.on_rejected();
+        assert_eq!(rate.rate(), 50.0);
+        cool_down(&rate);
+        rate.on_rejected();
+        assert_eq!(rate.rate(), 25.0);
+    }
+
+    #[test]
+    fn never_below_min() {
+        let rate = RateController::new(10.0, 100.0);
+        for _ in 0..10 {
+            rate.on_rejected();
+            cool_down(&rate);
+        }
+        assert_eq!(rate.rate(), 10.0);
+    }
+
+    #[test]
+    fn raised_by_a_second_of_txs() {
+        let rate = RateController::new(1.0, 100.0);
+        rate.on_rejected();
+        for _ in 0..49 {
+            rate.on_accepted();
+        }
+        assert_eq!(rate.rate(), 50.0);
+        rate.on_accepted();
+        assert_eq!(rate.rate(), 51.0);
+        // counted again from the last change
+        rate.on_accepted();
+        assert_eq!(rate.rate(), 51.0);
+    }
+
+    #[test]
+    fn never_above_max() {
+        let rate = RateController::new(1.0, 100.0);
+        for _ in 0..1000 {
+            rate.on_accepted();
+        }
+        assert_eq!(rate.rate(), 100.0);
+    }
+
+    #[test]
+    fn wait_after_rejection() {
+        let rate = RateController::new(1.0, 100.0);
+        assert_eq!(rate.reserve(), Duration::ZERO);
+        // the bucket is emptied by a cut
+        rate.on_rejected();
+        let wait = rate.reserve();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_millis(20), "{wait:?}");
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{
+    client::AsyncTestClient,
+    metrics::{now_millis, MetricsWriter, TxRecord},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::Duration,
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tracing::{debug, error, warn};
+use web3::{
+    futures::{stream, StreamExt},
+    types::{BlockId, BlockNumber, TransactionReceipt, H256},
+};
+
+/// A tx is taken as dropped if it's not included in this number of blocks after sent
+pub const DEFAULT_DROP_BLOCKS: u64 = 50;
+
+// how often new blocks are checked
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+// receipts fetched at the same time
+const RECEIPT_CONCURRENCY: usize = 64;
+// attempts to fetch a receipt before its status is taken as unknown
+const RECEIPT_RETRIES: u32 = 3;
+
+/// Inclusion results of tracked txs
+#[derive(Debug, Default, Clone)]
+pub struct TrackerReport {
+    pub tracked: u64,
+    pub included: u64,
+    /// included with a failed receipt status
+    pub failed: u64,
+    /// included, but its receipt was never fetched
+    pub unknown: u64,
+    /// never included within the drop window
+    pub dropped: u64,
+    /// total gas used by included txs
+    pub gas_used: u64,
+    /// milliseconds from sending to inclusion of included txs
+    latencies: Vec<u64>,
+}
+
+impl TrackerReport {
+    /// the `p`th (0-100) percentile of send-to-inclusion latency, milliseconds
+    pub fn percentile(&self, p: u8) -> Option<u64> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let mut latencies = self.latencies.clone();
+        latencies.sort_unstable();
+        let idx = (latencies.len() - 1) * p.min(100) as usize / 100;
+        Some(latencies[idx])
+    }
+
+    /// txs neither included nor dropped yet
+    pub fn pending(&self) -> u64 {
+        self.tracked - self.included - self.dropped
+    }
+}
+
+impl std::fmt::Display for TrackerReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |p| self.percentile(p).map(|l| l.to_string()).unwrap_or_default();
+        write!(
+            f,
+            "tracked,{},included,{},failed,{},unknown,{},dropped,{},pending,{},gas,{},p50,{},p90,{},p99,{}",
+            self.tracked,
+            self.included,
+            self.failed,
+            self.unknown,
+            self.dropped,
+            self.pending(),
+            self.gas_used,
+            ms(50),
+            ms(90),
+            ms(99),
+        )
+    }
+}
+
+#[derive(Debug)]
+struct PendingTx {
+    record: TxRecord,
+    // the height when the tx was sent
+    height: u64,
+}
+
+/// Resolve receipts of sent txs in the background by scanning new blocks.
+///
+/// The tracker runs on its own thread, so it keeps going while senders block their threads.
+#[derive(Debug)]
+pub struct ReceiptTracker {
+    sender: UnboundedSender<TxRecord>,
+    report: Arc<Mutex<TrackerReport>>,
+    handle: JoinHandle<()>,
+}
+
+impl ReceiptTracker {
+    /// start tracking, resolved txs are written to `metrics` if any
+    pub fn start(client: AsyncTestClient, drop_blocks: u64, metrics: Option<Arc<MetricsWriter>>) -> Self {
+        let (sender, receiver) = unbounded_channel();
+        let report = Arc::new(Mutex::new(TrackerReport::default()));
+        let worker = Worker {
+            client,
+            drop_blocks: drop_blocks.max(1),
+            metrics,
+            report: report.clone(),
+            pending: HashMap::new(),
+        };
+        let handle = std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            rt.block_on(worker.run(receiver));
+        });
+        Self { sender, report, handle }
+    }
+
+    /// track a sent tx, records without a hash are ignored
+    pub fn track(&self, record: TxRecord) {
+        if record.hash.is_some() && self.sender.send(record).is_err() {
+            warn!("receipt tracker stopped");
+        }
+    }
+
+    /// the results so far
+    pub fn report(&self) -> TrackerReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    /// wait until all tracked txs are included or dropped
+    pub fn finish(self) -> TrackerReport {
+        drop(self.sender);
+        if self.handle.join().is_err() {
+            error!("receipt tracker panicked");
+        }
+        let report = self.report.lock().unwrap().clone();
+        report
+    }
+}
+
+struct Worker {
+    client: AsyncTestClient,
+    drop_blocks: u64,
+    metrics: Option<Arc<MetricsWriter>>,
+    report: Arc<Mutex<TrackerReport>>,
+    pending: HashMap<H256, PendingTx>,
+}
+
+impl Worker {
+    async fn run(mut self, mut receiver: UnboundedReceiver<TxRecord>) {
+        let mut last = match self.client.block_number().await {
+            Some(height) => height.as_u64(),
+            None => {
+                error!("receipt tracker failed to get block number");
+                return;
+            }
+        };
+        let mut closed = false;
+        loop {
+            // drained after the height, so blocks up to it are scanned for all drained txs
+            let height = self.client.block_number().await.map(|h| h.as_u64()).unwrap_or(last);
+            while !closed {
+                match receiver.try_recv() {
+                    Ok(record) => self.add(record, last),
+                    Err(tokio::sync::mpsc::error::TryRecvError::Empty) => break,
+                    Err(tokio::sync::mpsc::error::TryRecvError::Disconnected) => closed = true,
+                }
+            }
+            if closed && self.pending.is_empty() {
+                break;
+            }
+
+            for number in last + 1..=height {
+                self.scan(number).await;
+                self.drop_expired(number).await;
+            }
+            last = height;
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        if let Some(metrics) = self.metrics.as_ref() {
+            if let Err(e) = metrics.flush() {
+                error!("failed to flush tx metrics {}", e);
+            }
+        }
+    }
+
+    fn add(&mut self, record: TxRecord, height: u64) {
+        if let Some(hash) = record.hash {
+            self.report.lock().unwrap().tracked += 1;
+            self.pending.insert(hash, PendingTx { record, height });
+        }
+    }
+
+    // resolve txs included in block `number`
+    async fn scan(&mut self, number: u64) {
+        let id = BlockId::Number(BlockNumber::Number(number.into()));
+        let block = match self.client.block_with_tx_hashes_inner(id, Some(1), Some(3)).await {
+            Some(block) => block,
+            None => {
+                warn!("receipt tracker failed to get block {}", number);
+                return;
+            }
+        };
+        let seen = now_millis();
+        let included = block
+            .transactions
+            .iter()
+            .filter_map(|hash| self.pending.remove(hash))
+            .collect::<Vec<_>>();
+        if included.is_empty() {
+            return;
+        }
+        debug!("block {} includes {} tracked txs", number, included.len());
+
+        for (tx, receipt) in self.receipts(included).await {
+            self.include(tx.record, number, seen, receipt);
+        }
+    }
+
+    // fetch receipts of `txs`, failed fetches are retried
+    async fn receipts(&self, txs: Vec<PendingTx>) -> Vec<(PendingTx, Option<TransactionReceipt>)> {
+        let client = &self.client;
+        stream::iter(txs)
+            .map(|tx| async move {
+                let hash = tx.record.hash.unwrap();
+                let mut receipt = None;
+                for attempt in 0..RECEIPT_RETRIES {
+                    if attempt > 0 {
+                        tokio::time::sleep(POLL_INTERVAL).await;
+                    }
+                    receipt = client.transaction_receipt(hash).await;
+                    if receipt.is_some() {
+                        break;
+                    }
+                }
+                (tx, receipt)
+            })
+            .buffer_unordered(RECEIPT_CONCURRENCY)
+            .collect::<Vec<_>>()
+            .await
+    }
+
+    // a tx included in block `number` and seen at `seen`
+    fn include(&self, mut record: TxRecord, number: u64, seen: u64, receipt: Option<TransactionReceipt>) {
+        let latency = seen.saturating_sub(record.send_time);
+        record.block = Some(number);
+        record.latency_ms = Some(latency);
+        record.status = receipt.as_ref().and_then(|r| r.status).map(|s| s.as_u64());
+        record.gas_used = receipt.as_ref().and_then(|r| r.gas_used).map(|g| g.low_u64());
+        {
+            let mut report = self.report.lock().unwrap();
+            report.included += 1;
+            report.gas_used += record.gas_used.unwrap_or_default();
+            report.latencies.push(latency);
+            match record.status {
+                Some(1) => {}
+                Some(_) => report.failed += 1,
+                None => report.unknown += 1,
+            }
+        }
+        self.write(&record);
+    }
+
+    // give up txs not included within the drop window, unless a receipt shows an inclusion missed by scans
+    async fn drop_expired(&mut self, height: u64) {
+        let drop_blocks = self.drop_blocks;
+        let expired = self
+            .pending
+            .iter()
+            .filter(|(_, tx)| tx.height + drop_blocks <= height)
+            .map(|(hash, _)| *hash)
+            .collect::<Vec<_>>();
+        if expired.is_empty() {
+            return;
+        }
+        let expired = expired
+            .iter()
+            .filter_map(|hash| self.pending.remove(hash))
+            .collect::<Vec<_>>();
+        let seen = now_millis();
+        for (tx, receipt) in self.receipts(expired).await {
+            match receipt.as_ref().and_then(|r| r.block_number) {
+                Some(number) => self.include(tx.record, number.as_u64(), seen, receipt),
+                None => {
+                    self.report.lock().unwrap().dropped += 1;
+                    let mut record = tx.record;
+                    record.error = Some("dropped".to_string());
+                    self.write(&record);
+                }
+            }
+        }
+    }
+
+    fn write(&self, record: &TxRecord) {
+        if let Some(metrics) = self.metrics.as_ref() {
+            if let Err(e) = metrics.write(record) {
+                warn!("failed to write tx metric {}", e);
+            }
+        }
+    }
+}
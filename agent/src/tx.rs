@@ -0,0 +1,112 @@
+use crate::gas::GasOracle;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use web3::types::{AccessList, TransactionParameters, U256, U64};
+
+/// Ethereum transaction types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TxType {
+    /// pre-EIP-2718 transaction with a gas price
+    #[default]
+    Legacy,
+    /// EIP-2930 transaction with an access list
+    AccessList,
+    /// EIP-1559 transaction with a max fee and a priority fee
+    DynamicFee,
+}
+
+impl TxType {
+    pub const ALL: [TxType; 3] = [TxType::Legacy, TxType::AccessList, TxType::DynamicFee];
+
+    /// the EIP-2718 type id, `None` for legacy transactions
+    pub fn type_id(&self) -> Option<U64> {
+        match self {
+            Self::Legacy => None,
+            Self::AccessList => Some(U64::from(1)),
+            Self::DynamicFee => Some(U64::from(2)),
+        }
+    }
+
+    /// position in `TxType::ALL`
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl std::fmt::Display for TxType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Legacy => write!(f, "legacy"),
+            Self::AccessList => write!(f, "access-list"),
+            Self::DynamicFee => write!(f, "dynamic-fee"),
+        }
+    }
+}
+
+impl std::str::FromStr for TxType {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().trim() {
+            "legacy" | "0" => Ok(Self::Legacy),
+            "access-list" | "eip2930" | "1" => Ok(Self::AccessList),
+            "dynamic-fee" | "eip1559" | "2" => Ok(Self::DynamicFee),
+            n => Err(format!("invalid tx type {n}")),
+        }
+    }
+}
+
+/// Fee settings of a transaction, unset prices are filled by the node
+#[derive(Debug, Clone, Default)]
+pub struct TxFee {
+    pub tx_type: TxType,
+    /// gas price of legacy and access-list transactions
+    pub gas_price: Option<U256>,
+    /// max fee per gas of dynamic-fee transactions
+    pub max_fee_per_gas: Option<U256>,
+    /// max priority fee per gas of dynamic-fee transactions
+    pub max_priority_fee_per_gas: Option<U256>,
+    /// access list of access-list and dynamic-fee transactions
+    pub access_list: Option<AccessList>,
+    /// prices the gas of each tx, it takes precedence over `gas_price`
+    pub oracle: Option<Arc<GasOracle>>,
+}
+
+impl TxFee {
+    pub fn legacy(gas_price: Option<U256>) -> Self {
+        Self {
+            gas_price,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_oracle(mut self, oracle: Arc<GasOracle>) -> Self {
+        self.oracle = Some(oracle);
+        self
+    }
+
+    /// set type and fee fields of a transaction
+    pub fn apply(&self, tx: &mut TransactionParameters) {
+        let (gas_price, max_fee, priority_fee, access_list) = match self.tx_type {
+            TxType::Legacy => (self.gas_price, None, None, None),
+            TxType::AccessList => (
+                self.gas_price,
+                None,
+                None,
+                Some(self.access_list.clone().unwrap_or_default()),
+            ),
+            TxType::DynamicFee => (
+                None,
+                self.max_fee_per_gas.or(self.gas_price),
+                self.max_priority_fee_per_gas,
+                self.access_list.clone(),
+            ),
+        };
+        tx.transaction_type = self.tx_type.type_id();
+        tx.gas_price = gas_price;
+        tx.max_fee_per_gas = max_fee;
+        tx.max_priority_fee_per_gas = priority_fee;
+        tx.access_list = access_list;
+    }
+}
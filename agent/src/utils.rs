@@ -1,8 +1,11 @@
-use crate::{one_eth_key, KeyPair, TestClient};
+use crate::{
+    error::{Error, Result},
+    hd, keystore, one_eth_key, NonceManager, TestClient,
+};
 use rayon::prelude::*;
 use sha3::{Digest, Keccak256};
 use std::{ops::Mul, path::Path, str::FromStr, sync::Arc, time::Duration};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use url::Url;
 use web3::types::{Address, H256, U256};
 
@@ -39,20 +42,26 @@ pub fn real_network(network: &str) -> Vec<Option<String>> {
     }
 }
 
-#[inline(always)]
-pub fn extract_keypair_from_file<P>(secret: P) -> (secp256k1::SecretKey, Address)
+/// Read a secret key file, either a hex key or a keystore
+pub fn extract_keypair_from_file<P>(secret: P) -> Result<(secp256k1::SecretKey, Address)>
 where
     P: AsRef<Path>,
 {
-    let sk_str = std::fs::read_to_string(secret).unwrap();
-    let root_sk = secp256k1::SecretKey::from_str(sk_str.trim()).unwrap();
+    let data = keystore::read_secret(secret)?;
+    let root_sk = match secp256k1::SecretKey::from_slice(&data) {
+        Ok(sk) => sk,
+        Err(_) => {
+            let sk_str = String::from_utf8(data).map_err(|e| Error::Other(e.to_string()))?;
+            secp256k1::SecretKey::from_str(sk_str.trim()).map_err(|e| Error::Other(e.to_string()))?
+        }
+    };
     let s = secp256k1::Secp256k1::signing_only();
     let root_pk = secp256k1::PublicKey::from_secret_key(&s, &root_sk);
     let mut res = [0u8; 64];
     res.copy_from_slice(&root_pk.serialize_uncompressed()[1..65]);
     let root_addr = Address::from(H256::from_slice(Keccak256::digest(res).as_slice()));
 
-    (root_sk, root_addr)
+    Ok((root_sk, root_addr))
 }
 
 pub fn check_parallel_args(max_par: u64) {
@@ -76,7 +85,16 @@ pub fn calc_pool_size(keys: usize, max_par: usize) -> usize {
     max_pool_size
 }
 
+/// The `index`th target of `source`, derived from `seed` if any, or a random one
+pub fn target_address(seed: Option<u64>, source: &Address, index: u64) -> Address {
+    match seed {
+        Some(seed) => hd::target_address(seed, source, index),
+        None => Address::from_str(one_eth_key().address.as_str()).unwrap(),
+    }
+}
+
 #[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
 pub fn build_source_keys<P>(
     client: Arc<TestClient>,
     source_file: P,
@@ -85,12 +103,12 @@ pub fn build_source_keys<P>(
     source_count: Option<u64>,
     count: u64,
     max_par: u64,
-) -> Vec<(secp256k1::SecretKey, Address, Vec<(Address, U256)>)>
+    target_seed: Option<u64>,
+) -> Result<Vec<(secp256k1::SecretKey, Address, Vec<(Address, U256)>)>>
 where
     P: AsRef<Path>,
 {
-    let source_keys: Vec<KeyPair> =
-        serde_json::from_str(std::fs::read_to_string(source_file).unwrap().as_str()).unwrap();
+    let source_keys = hd::read_eth_keys(source_file)?;
 
     let max_pool_size = calc_pool_size(source_keys.len(), max_par as usize);
     rayon::ThreadPoolBuilder::new()
@@ -113,12 +131,7 @@ where
             };
             if balance > target_amount.mul(count) {
                 let target = (0..count)
-                    .map(|_| {
-                        (
-                            Address::from_str(one_eth_key().address.as_str()).unwrap(),
-                            target_amount,
-                        )
-                    })
+                    .map(|i| (target_address(target_seed, &address, i), target_amount))
                     .collect::<Vec<_>>();
                 debug!("account {:?} added to source pool", address);
                 Some((secret, address, target))
@@ -134,7 +147,7 @@ where
         }
     }
 
-    source_keys
+    Ok(source_keys)
 }
 
 pub fn display_info(client: Arc<TestClient>) -> (u64, U256) {
@@ -153,3 +166,14 @@ pub fn wait_receipt(client: Arc<TestClient>, hash: H256) -> bool {
 
     receipt.is_some()
 }
+
+/// log mismatched nonces of `addresses`, returns the count of accounts with a gap
+pub fn log_nonce_gaps<I>(client: Arc<TestClient>, nonces: &NonceManager, addresses: I) -> usize
+where
+    I: Iterator<Item = Address>,
+{
+    addresses
+        .filter_map(|address| client.rt.block_on(nonces.detect_gap(address)).ok().flatten())
+        .inspect(|gap| warn!("nonce gap: {}", gap))
+        .count()
+}
@@ -1,4 +1,4 @@
-use agent::error::Result;
+use agent::{error::Result, keystore};
 use std::path::Path;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
 
@@ -11,6 +11,11 @@ pub(crate) async fn read_mnemonics<P>(secret: P, mut mnemonics: Vec<String>) ->
 where
     P: AsRef<Path>,
 {
+    if keystore::is_keystore(&secret) {
+        let data = keystore::read_to_string(secret)?;
+        mnemonics.extend(data.lines().map(|l| l.to_owned()));
+        return Ok(mnemonics);
+    }
     let file = tokio::fs::OpenOptions::new().read(true).open(secret).await?;
     let mut lines = tokio::io::BufReader::new(file).lines();
     while let Some(line) = lines.next_line().await? {
@@ -20,10 +25,14 @@ where
     Ok(mnemonics)
 }
 
-pub(crate) async fn write_mnemonics<P>(secret: P, mnemonics: Vec<String>) -> Result<Vec<String>>
+pub(crate) async fn write_mnemonics<P>(secret: P, mnemonics: Vec<String>, password: Option<&str>) -> Result<Vec<String>>
 where
     P: AsRef<Path>,
 {
+    if password.is_some() {
+        keystore::write_secret(secret, mnemonics.join("\n").as_bytes(), password)?;
+        return Ok(mnemonics);
+    }
     let file = tokio::fs::OpenOptions::new()
         .write(true)
         .create_new(true)
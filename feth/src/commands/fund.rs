@@ -1,17 +1,73 @@
 use super::common::{read_mnemonics, write_mnemonics, ETH_SECRET, ETH_SOURCE_FILE, UTXO_SECRET, UTXO_SOURCE_FILE};
 use agent::{
     error::{Error, Result},
-    native::{generate_mnemonic, restore_fra_keypair, restore_keypair_from_mnemonic_default, transfer_batch, FRA},
-    one_eth_key, TestClient, TestClientOpts, BLOCK_TIME,
+    hd::{self, HdKeys},
+    keystore,
+    native::{
+        generate_mnemonic, restore_fra_keypair, restore_keypair_from_mnemonic_default, transfer_batch, XfrKeyPair, FRA,
+    },
+    one_eth_key, EndpointPolicy, MetricsWriter, TestClient, TestClientOpts, TxFee, BLOCK_TIME,
 };
 use std::{
     ops::{Mul, MulAssign},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 use tracing::{debug, info};
 use web3::types::Address;
 
+/// How to derive source keys from one master mnemonic
+#[derive(Debug, Clone, Default)]
+pub struct HdOpts {
+    /// the file of the master mnemonic, a new one is generated if it's not given
+    pub mnemonic: Option<PathBuf>,
+    /// the index of the first key
+    pub start: u32,
+}
+
+impl HdOpts {
+    // keys of the master mnemonic, `new_mnemonic` generates one if there's no mnemonic file
+    fn keys<F>(&self, count: u64, new_mnemonic: F) -> Result<HdKeys>
+    where
+        F: FnOnce() -> Result<String>,
+    {
+        let mnemonic = match self.mnemonic.as_ref() {
+            Some(file) => keystore::read_to_string(file)?.trim().to_owned(),
+            None => new_mnemonic()?,
+        };
+        Ok(HdKeys {
+            mnemonic,
+            start: self.start,
+            count: count as u32,
+        })
+    }
+}
+
+// the passphrase to write a key file, an encrypted key file is kept encrypted
+fn key_file_password(file: &Path, encrypt: bool) -> Result<Option<String>> {
+    if keystore::is_keystore(file) {
+        keystore::passphrase(file).map(Some)
+    } else if encrypt {
+        keystore::new_passphrase(file).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+// extend `keys` to `count` keys and save them, by a backup file to keep the original one on failure
+fn extend_hd_keys(file: &Path, mut keys: HdKeys, count: u64) -> Result<HdKeys> {
+    if count as u32 <= keys.count {
+        return Ok(keys);
+    }
+    let password = key_file_password(file, false)?;
+    let mut file_bak = file.to_path_buf();
+    file_bak.set_extension(".bak");
+    keys.count = count as u32;
+    keys.write(&file_bak, password.as_deref())?;
+    std::fs::rename(file_bak, file)?;
+    Ok(keys)
+}
+
 #[allow(clippy::too_many_arguments)]
 pub fn fund_utxo_accounts(
     network: &str,
@@ -19,6 +75,8 @@ pub fn fund_utxo_accounts(
     count: u64,
     amount: u64,
     load: bool,
+    hd_opts: Option<HdOpts>,
+    encrypt: bool,
 ) -> Result<()> {
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
@@ -28,12 +86,47 @@ pub fn fund_utxo_accounts(
     let amount = amount * FRA;
     let source_keys_file = source_keys_file.unwrap_or(PathBuf::from_str(UTXO_SOURCE_FILE).unwrap());
     let owner_kp = restore_fra_keypair(UTXO_SECRET)?;
+    let kps = if load {
+        match HdKeys::read(&source_keys_file) {
+            Ok(keys) => extend_hd_keys(&source_keys_file, keys, count)?.fra_keys()?,
+            Err(_) => load_utxo_mnemonics(&runtime, &source_keys_file, count, load, encrypt)?,
+        }
+    } else if let Some(hd) = hd_opts {
+        if source_keys_file.exists() {
+            return Err(Error::Other("source keys file already existed".to_string()));
+        }
+        let keys = hd.keys(count, || Ok(generate_mnemonic(1, 24, "en")?.remove(0)))?;
+        keys.write(
+            &source_keys_file,
+            key_file_password(&source_keys_file, encrypt)?.as_deref(),
+        )?;
+        keys.fra_keys()?
+    } else {
+        load_utxo_mnemonics(&runtime, &source_keys_file, count, load, encrypt)?
+    };
+    info!("{} accounts loaded to be fund", kps.len());
+
+    let target_list = kps.iter().map(|p| (&p.pub_key, amount)).collect::<Vec<_>>();
+
+    transfer_batch(network, owner_kp, target_list)?;
+
+    Ok(())
+}
+
+fn load_utxo_mnemonics(
+    runtime: &tokio::runtime::Runtime,
+    source_keys_file: &Path,
+    count: u64,
+    load: bool,
+    encrypt: bool,
+) -> Result<Vec<XfrKeyPair>> {
+    let password = key_file_password(source_keys_file, encrypt)?;
     let mnemonics = if load {
-        let mut mnemonics = runtime.block_on(async { read_mnemonics(&source_keys_file, vec![]).await })?;
+        let mut mnemonics = runtime.block_on(async { read_mnemonics(source_keys_file, vec![]).await })?;
         if count as usize > mnemonics.len() {
             mnemonics.append(&mut generate_mnemonic(count as usize - mnemonics.len(), 24, "en")?);
             //write new keys back
-            runtime.block_on(async { write_mnemonics(&source_keys_file, mnemonics).await })?
+            runtime.block_on(async { write_mnemonics(source_keys_file, mnemonics, password.as_deref()).await })?
         } else {
             mnemonics
         }
@@ -43,20 +136,15 @@ pub fn fund_utxo_accounts(
         }
         let mn = generate_mnemonic(count as usize, 24, "en")?;
         //write new keys back
-        runtime.block_on(async { write_mnemonics(&source_keys_file, mn.clone()).await })?
+        runtime.block_on(async { write_mnemonics(source_keys_file, mn.clone(), password.as_deref()).await })?
     };
-    info!("{} accounts loaded to be fund", mnemonics.len());
 
     let mut kps = vec![];
     for mn in mnemonics {
         let kp = restore_keypair_from_mnemonic_default(mn.trim()).map_err(|o| Error::Other(o.to_string()))?;
         kps.push(kp);
     }
-    let target_list = kps.iter().map(|p| (&p.pub_key, amount)).collect::<Vec<_>>();
-
-    transfer_batch(network, owner_kp, target_list)?;
-
-    Ok(())
+    Ok(kps)
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -69,6 +157,10 @@ pub fn fund_accounts(
     redeposit: bool,
     seq: bool,
     delay_in_seconds: u64,
+    policy: EndpointPolicy,
+    metrics: &MetricsWriter,
+    hd_opts: Option<HdOpts>,
+    encrypt: bool,
 ) {
     let source_keys_file = source_keys_file.unwrap_or(PathBuf::from_str(ETH_SOURCE_FILE).unwrap());
     let mut amount = web3::types::U256::exp10(17); // 0.1 eth
@@ -78,14 +170,23 @@ pub fn fund_accounts(
         endpoint_url: Some(network.to_string()),
         secret_file: Some(ETH_SECRET.to_owned()),
         timeout: Some(20),
+        policy,
+        ..Default::default()
     };
-    let client = TestClient::setup_with_opts(opts);
-    let balance = client.balance(client.root_addr, None);
-    info!("Balance of {:?}: {}", client.root_addr, balance);
+    let client = TestClient::setup_with_opts(opts).expect("failed to connect to the network");
+    let (root_sk, root_addr) = client.root().unwrap();
+    let balance = client.balance(root_addr, None);
+    info!("Balance of {:?}: {}", root_addr, balance);
 
     let mut source_keys = if load {
-        let keys: Vec<_> = serde_json::from_str(std::fs::read_to_string(&source_keys_file).unwrap().as_str()).unwrap();
-        keys
+        if let Ok(keys) = HdKeys::read(&source_keys_file) {
+            // add more source keys by extending the index range
+            extend_hd_keys(&source_keys_file, keys, count)
+                .and_then(|keys| keys.eth_keys())
+                .unwrap()
+        } else {
+            hd::read_eth_keys(&source_keys_file).unwrap()
+        }
     } else {
         // check if the key file exists
         debug!("generating new source keys");
@@ -95,22 +196,32 @@ pub fn fund_accounts(
         if amount.mul(count + 1) >= balance {
             panic!("Too large source account number, maximum {}", balance / amount);
         }
-        let source_keys = (0..count).map(|_| one_eth_key()).collect::<Vec<_>>();
-        let data = serde_json::to_string(&source_keys).unwrap();
-        std::fs::write(&source_keys_file, data).unwrap();
+        if let Some(hd) = hd_opts {
+            let keys = hd.keys(count, || Ok(HdKeys::generate(hd.start, 0).mnemonic)).unwrap();
+            let password = key_file_password(&source_keys_file, encrypt).unwrap();
+            keys.write(&source_keys_file, password.as_deref()).unwrap();
+            info!("{} source keys derived from {}", keys.count, hd::eth_path(keys.start));
+            keys.eth_keys().unwrap()
+        } else {
+            let source_keys = (0..count).map(|_| one_eth_key()).collect::<Vec<_>>();
+            let data = serde_json::to_string(&source_keys).unwrap();
+            let password = key_file_password(&source_keys_file, encrypt).unwrap();
+            keystore::write_secret(&source_keys_file, data.as_bytes(), password.as_deref()).unwrap();
 
-        source_keys
+            source_keys
+        }
     };
 
     // add more source keys and save them to file
     if count as usize > source_keys.len() {
+        let password = key_file_password(&source_keys_file, false).unwrap();
         let mut file_bak = source_keys_file.clone();
         file_bak.set_extension(".bak");
 
         source_keys.resize_with(count as usize, one_eth_key);
 
         let data = serde_json::to_string(&source_keys).unwrap();
-        std::fs::write(file_bak.clone(), data).unwrap();
+        keystore::write_secret(&file_bak, data.as_bytes(), password.as_deref()).unwrap();
 
         // replace original file
         std::fs::rename(file_bak, source_keys_file).unwrap();
@@ -140,13 +251,28 @@ pub fn fund_accounts(
         .collect::<Vec<_>>();
     // 1000 eth
     if seq {
-        client
+        let records = client
             .rt
-            .block_on(client.distribute(&client.root_sk, &source_accounts, delay_in_seconds))
+            .block_on(client.distribute(&root_sk, &source_accounts, delay_in_seconds, &TxFee::default()))
             .unwrap();
+        for record in records {
+            metrics.write(&record).unwrap();
+        }
     } else {
-        let _metrics = client
-            .distribution(1, None, &source_accounts, &Some(BLOCK_TIME), true, true)
+        let metrics_of_txs = client
+            .distribution(
+                1,
+                None,
+                &source_accounts,
+                &Some(BLOCK_TIME),
+                true,
+                true,
+                &TxFee::default(),
+            )
             .unwrap();
+        metrics_of_txs.write_to(metrics).unwrap();
     }
+    metrics.flush().unwrap();
+    info!("tx metrics saved to {:?}", metrics.path());
+    client.inner.pool.log_report();
 }
@@ -0,0 +1,92 @@
+use super::Network;
+use agent::{
+    abi,
+    error::{Error, Result},
+    BenchContract, LogQuery, Manifest, TestClient,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use web3::{ethabi::Contract, types::Address};
+
+/// The contract whose logs are fetched, and the ABI to decode them
+#[derive(Debug, Clone)]
+pub struct LogsOpts {
+    pub address: Option<Address>,
+    /// a contract in the deployment manifest, in place of `address` and the ABI
+    pub contract: Option<String>,
+    /// the manifest file, `deployments.<chain_id>.json` by default
+    pub manifest: Option<PathBuf>,
+    pub builtin: Option<BenchContract>,
+    pub abi: Option<PathBuf>,
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    pub batch: u64,
+}
+
+fn load_abi(path: &Path) -> Result<Contract> {
+    abi::load_abi(path).map_err(|e| Error::Other(format!("{e:#}")))
+}
+
+/// Write the logs of a contract to `output` in JSONL, decoded by its ABI if known.
+///
+/// Explicit options take precedence over the manifest entry of `contract`.
+pub fn watch_logs(network: &Network, timeout: Option<u64>, opts: &LogsOpts, follow: bool, output: &Path) -> Result<()> {
+    let client = TestClient::setup(Some(network.eth_url()), timeout)?;
+
+    let deployed = match opts.contract.as_ref() {
+        Some(name) => {
+            let chain_id = client
+                .chain_id()
+                .ok_or_else(|| Error::Other("failed to get the chain id".to_string()))?
+                .as_u64();
+            let path = opts
+                .manifest
+                .clone()
+                .unwrap_or_else(|| Manifest::default_path(chain_id));
+            Some(Manifest::load(path, chain_id)?.get(name)?.clone())
+        }
+        None => None,
+    };
+    let address = opts
+        .address
+        .or_else(|| deployed.as_ref().map(|d| d.address))
+        .ok_or_else(|| Error::Other("a contract address or name is required".to_string()))?;
+    let abi = match (opts.builtin, opts.abi.as_ref(), deployed.as_ref()) {
+        (Some(builtin), _, _) => Some(builtin.abi()),
+        (None, Some(path), _) => Some(load_abi(path)?),
+        (None, None, Some(deployed)) => match deployed.builtin {
+            Some(builtin) => Some(builtin.abi()),
+            None if !deployed.abi_path.is_empty() => Some(load_abi(Path::new(&deployed.abi_path))?),
+            None => None,
+        },
+        (None, None, None) => None,
+    };
+    if abi.is_none() {
+        info!("no ABI of {:?}, logs are written undecoded", address);
+    }
+
+    let query = LogQuery {
+        address,
+        from_block: opts.from_block,
+        to_block: opts.to_block,
+        batch: opts.batch,
+    };
+    info!("fetching logs of {:?}, follow {}", address, follow);
+    let mut writer = BufWriter::new(File::create(output)?);
+    let count = client.watch_logs(abi.as_ref(), &query, follow, |log| {
+        let line = serde_json::to_string(&log).map_err(|e| Error::Other(e.to_string()))?;
+        writeln!(writer, "{line}")?;
+        // a follower may be stopped at any time
+        if follow {
+            writer.flush()?;
+        }
+        Ok(())
+    })?;
+    writer.flush()?;
+    info!("{} logs written to {:?}", count, output);
+    Ok(())
+}
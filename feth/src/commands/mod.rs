@@ -1,19 +1,24 @@
 mod common;
 mod fund;
+mod logs;
 mod native;
 mod prism;
+mod replay;
 mod tests;
 
 pub use fund::*;
+pub use logs::*;
 pub use native::*;
 pub use prism::*;
+pub use replay::*;
 pub use tests::*;
 
 use agent::{
     db::{Db, Proto},
     error::Result,
+    logs::DEFAULT_LOG_BATCH,
     native::{NativeOp, PrismOp},
-    profiler,
+    profiler, BenchContract, EndpointPolicy, GasStrategy, MetricsFormat, TxType,
 };
 use chrono::NaiveDateTime;
 use clap::{Parser, Subcommand};
@@ -34,6 +39,16 @@ pub enum TestMode {
     Long,
 }
 
+impl Display for TestMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Basic => write!(f, "basic"),
+            Self::Contract => write!(f, "contract"),
+            Self::Long => write!(f, "long"),
+        }
+    }
+}
+
 impl std::str::FromStr for TestMode {
     type Err = String;
 
@@ -42,7 +57,7 @@ impl std::str::FromStr for TestMode {
             "basic" => Ok(Self::Basic),
             "contract" => Ok(Self::Contract),
             "long" => Ok(Self::Long),
-            _ => Err("Invalid mode: basic and contract are supported".to_owned()),
+            _ => Err("Invalid mode: basic, contract and long are supported".to_owned()),
         }
     }
 }
@@ -397,6 +412,49 @@ pub enum Commands {
         /// fund in a sequential way or not
         #[clap(long)]
         seq: bool,
+
+        /// how to spread requests over endpoints: 'round-robin', 'sticky', 'failover'
+        #[clap(long, default_value_t = EndpointPolicy::Sticky)]
+        policy: EndpointPolicy,
+
+        /// the directory of per-tx metric files
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = ".")]
+        metrics_dir: PathBuf,
+
+        /// the format of per-tx metric files: 'csv', 'jsonl'
+        #[clap(long, default_value_t = MetricsFormat::Csv)]
+        metrics_format: MetricsFormat,
+
+        /// derive new source keys from one master mnemonic at m/44'/60'/0'/0/i, m/44'/917'/0'/0/i for utxo
+        #[clap(long)]
+        hd: bool,
+
+        /// the master mnemonic file of derived source keys, a new one is generated by default
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        mnemonic: Option<PathBuf>,
+
+        /// the index of the first derived source key
+        #[clap(long, default_value_t = 0)]
+        start_index: u32,
+
+        /// encrypt new source key files as keystores, the passphrase is read from FETH_KEYSTORE_PASSWORD or a prompt
+        #[clap(long)]
+        encrypt: bool,
+    },
+
+    /// Encrypt a secret file to a keystore, or decrypt a keystore
+    Keystore {
+        /// a hex private key, a mnemonic, or a source key file; a keystore to decrypt
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        input: PathBuf,
+
+        /// the keystore; the plaintext file of a decrypted keystore
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        output: PathBuf,
+
+        /// decrypt the input keystore
+        #[clap(long)]
+        decrypt: bool,
     },
     /// check ethereum account information
     Info {
@@ -451,6 +509,57 @@ pub enum Commands {
         follow: bool,
     },
 
+    /// Contract event logs, decoded by the contract ABI
+    Logs {
+        /// ethereum-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// http request timeout, seconds
+        #[clap(long)]
+        timeout: Option<u64>,
+
+        /// the contract address
+        #[clap(long)]
+        address: Option<Address>,
+
+        /// a contract name in the deployment manifest, in place of `--address` and `--abi`
+        #[clap(long)]
+        contract: Option<String>,
+
+        /// the deployment manifest, `deployments.<chain_id>.json` by default
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        manifest: Option<PathBuf>,
+
+        /// the ABI of a contract shipped with feth: 'erc20', 'storage', 'compute', 'emitter'
+        #[clap(long)]
+        builtin: Option<BenchContract>,
+
+        /// the ABI file, logs are written undecoded without an ABI
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        abi: Option<PathBuf>,
+
+        /// the first block, the latest by default
+        #[clap(long)]
+        from_block: Option<u64>,
+
+        /// the last block, the latest by default, or no end in follow mode
+        #[clap(long)]
+        to_block: Option<u64>,
+
+        /// the blocks of an eth_getLogs request
+        #[clap(long, default_value_t = DEFAULT_LOG_BATCH)]
+        batch: u64,
+
+        /// keep fetching the logs of new blocks
+        #[clap(short, long)]
+        follow: bool,
+
+        /// the JSONL file of logs
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "logs.jsonl")]
+        output: PathBuf,
+    },
+
     /// ETL procession
     Etl {
         /// abcid log file
@@ -510,7 +619,7 @@ pub enum Commands {
         #[clap(long)]
         mode: TestMode,
 
-        /// TxnsType: 'eth', 'utxo', 'prism', 'mixed,u64,u64,u64'
+        /// TxnsType: 'eth', 'utxo', 'prism', 'staking', 'mixed,u64,u64,u64'
         #[clap(long, default_value_t = TxnsType::Eth)]
         txns_type: TxnsType,
 
@@ -542,13 +651,188 @@ pub enum Commands {
         #[clap(long)]
         check_balance: bool,
 
-        /// if need to wait for the tx receipt
+        /// track receipts of sent txs in the background, and report inclusion latency
         #[clap(long)]
         wait_receipt: bool,
 
         /// if need to fetch block info
         #[clap(long)]
         fetch_block: bool,
+
+        /// how to spread requests over endpoints: 'round-robin', 'sticky', 'failover'
+        #[clap(long, default_value_t = EndpointPolicy::RoundRobin)]
+        policy: EndpointPolicy,
+
+        /// The count of consecutive-nonce transactions sent by a source key per round
+        #[clap(long, default_value_t = 1)]
+        txs_per_account: u64,
+
+        /// Transaction types sent in turn: 'legacy', 'access-list', 'dynamic-fee'
+        #[clap(long, value_delimiter = ',', default_value = "legacy")]
+        tx_type: Vec<TxType>,
+
+        /// max fee per gas of dynamic-fee transactions, wei, the gas price of the node by default
+        #[clap(long)]
+        max_fee: Option<u64>,
+
+        /// max priority fee per gas of dynamic-fee transactions, wei
+        #[clap(long)]
+        priority_fee: Option<u64>,
+
+        /// the access list file of access-list and dynamic-fee transactions, in json
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        access_list: Option<PathBuf>,
+
+        /// gas pricing: 'fixed:<wei>', 'node', 'refresh:<blocks>', 'percentile:<p>[:<blocks>]',
+        /// the gas price at start by default, 'refresh:10' for long runs
+        #[clap(long)]
+        gas_strategy: Option<GasStrategy>,
+
+        /// the highest send rate of all source keys, txs per second, it's lowered when the mempool is full
+        #[clap(long)]
+        max_rate: Option<f64>,
+
+        /// the directory of per-tx metric files
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = ".")]
+        metrics_dir: PathBuf,
+
+        /// the format of per-tx metric files: 'csv', 'jsonl'
+        #[clap(long, default_value_t = MetricsFormat::Csv)]
+        metrics_format: MetricsFormat,
+
+        /// derive target addresses from this seed, random targets by default
+        #[clap(long)]
+        target_seed: Option<u64>,
+
+        /// staking txns type: tendermint addresses of the validators to delegate to
+        #[clap(long, value_delimiter = ',')]
+        validators: Vec<String>,
+
+        /// contract mode: the contract to call, it's deployed from `--builtin` or `--code` if not given
+        #[clap(long)]
+        contract: Option<Address>,
+
+        /// contract mode: a contract shipped with feth: 'erc20', 'storage', 'compute', 'emitter'
+        #[clap(long)]
+        builtin: Option<BenchContract>,
+
+        /// contract mode: the ABI file of the contract
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        abi: Option<PathBuf>,
+
+        /// contract mode: the hex bytecode file to deploy the contract from, by the first source key
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        code: Option<PathBuf>,
+
+        /// contract mode: constructor arguments, a JSON array
+        #[clap(long, default_value = "[]")]
+        deploy_args: String,
+
+        /// contract mode: the function to call, a name or a signature such as 'transfer(address,uint256)'
+        #[clap(long)]
+        function: Option<String>,
+
+        /// contract mode: function arguments, a JSON array
+        #[clap(long, default_value = "[]")]
+        args: String,
+
+        /// contract mode: the gas limit of calls, estimated by default
+        #[clap(long)]
+        gas: Option<u64>,
+    },
+
+    /// Sign transfers of source keys offline with local nonces, to be replayed later
+    Sign {
+        /// Ethereum web3-compatible network, for nonces, gas price and chain id
+        #[clap(long)]
+        network: Network,
+
+        /// the source account file
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "source_keys.001")]
+        source: PathBuf,
+
+        /// The count of used source keys
+        #[clap(long)]
+        source_count: Option<u64>,
+
+        /// The count of transactions signed by a source key
+        #[clap(long, default_value_t = 1)]
+        count: u64,
+
+        /// The max thread pool size
+        #[clap(long, default_value_t = 200)]
+        max_threads: u64,
+
+        /// http request timeout, seconds
+        #[clap(long, default_value_t = 60)]
+        timeout: u64,
+
+        /// Transaction types signed in turn: 'legacy', 'access-list', 'dynamic-fee'
+        #[clap(long, value_delimiter = ',', default_value = "legacy")]
+        tx_type: Vec<TxType>,
+
+        /// max fee per gas of dynamic-fee transactions, wei, the gas price of the node by default
+        #[clap(long)]
+        max_fee: Option<u64>,
+
+        /// max priority fee per gas of dynamic-fee transactions, wei
+        #[clap(long)]
+        priority_fee: Option<u64>,
+
+        /// the access list file of access-list and dynamic-fee transactions, in json
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        access_list: Option<PathBuf>,
+
+        /// gas pricing at signing time: 'fixed:<wei>', 'node', 'percentile:<p>[:<blocks>]'
+        #[clap(long)]
+        gas_strategy: Option<GasStrategy>,
+
+        /// derive target addresses from this seed, random targets by default
+        #[clap(long)]
+        target_seed: Option<u64>,
+
+        /// the file of signed transactions
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "signed_txs.jsonl")]
+        output: PathBuf,
+    },
+
+    /// Send signed transactions of `feth sign` with send_raw_transaction only
+    Replay {
+        /// Ethereum web3-compatible network
+        #[clap(long)]
+        network: Network,
+
+        /// the file of signed transactions
+        #[clap(long, parse(from_os_str), value_name = "FILE", default_value = "signed_txs.jsonl")]
+        input: PathBuf,
+
+        /// The max thread pool size
+        #[clap(long, default_value_t = 200)]
+        max_threads: u64,
+
+        /// http request timeout, seconds
+        #[clap(long, default_value_t = 60)]
+        timeout: u64,
+
+        /// how to spread requests over endpoints: 'round-robin', 'sticky', 'failover'
+        #[clap(long, default_value_t = EndpointPolicy::RoundRobin)]
+        policy: EndpointPolicy,
+
+        /// the highest send rate of all source keys, txs per second, it's lowered when the mempool is full
+        #[clap(long)]
+        max_rate: Option<f64>,
+
+        /// track receipts of sent txs in the background, and report inclusion latency
+        #[clap(long)]
+        wait_receipt: bool,
+
+        /// the directory of per-tx metric files
+        #[clap(long, parse(from_os_str), value_name = "DIR", default_value = ".")]
+        metrics_dir: PathBuf,
+
+        /// the format of per-tx metric files: 'csv', 'jsonl'
+        #[clap(long, default_value_t = MetricsFormat::Csv)]
+        metrics_format: MetricsFormat,
     },
 
     /// Findora prism operations
@@ -572,6 +856,18 @@ pub enum Commands {
         /// amount to deposit or withdraw
         #[clap(long)]
         amount: u64,
+
+        /// asset code in base64, FRA by default
+        #[clap(long)]
+        asset: Option<String>,
+
+        /// decimals of the asset definition, to withdraw an asset
+        #[clap(long, default_value_t = 6)]
+        decimal: u8,
+
+        /// max units of the asset definition, to withdraw an asset, 0 for no limit
+        #[clap(long, default_value_t = 0)]
+        max_supply: u64,
     },
 
     /// Findora utxo transactions
@@ -580,7 +876,7 @@ pub enum Commands {
         #[clap(long)]
         network: Network,
 
-        /// native operation
+        /// native operation: 'transfer', 'delegate', 'undelegate', 'claim', 'stake', 'define-asset', 'issue-asset'
         #[clap(long)]
         op: NativeOp,
 
@@ -588,12 +884,40 @@ pub enum Commands {
         #[clap(long, parse(from_os_str))]
         secret: PathBuf,
 
-        /// target address to receive tokens
+        /// target address to receive tokens, required by transfer; the issuer by default to issue an asset
         #[clap(long)]
-        target: String,
+        target: Option<String>,
 
-        /// amount to deposit or withdraw
+        /// amount to transfer, delegate, stake or issue; to undelegate or claim, all by default
         #[clap(long)]
-        amount: u64,
+        amount: Option<u64>,
+
+        /// tendermint address of the validator to delegate to, undelegate or claim from
+        #[clap(long)]
+        validator: Option<String>,
+
+        /// priv_validator_key.json of the tendermint node to stake
+        #[clap(long, parse(from_os_str), value_name = "FILE")]
+        validator_key: Option<PathBuf>,
+
+        /// commission rate of a staking validator, within [0, 1]
+        #[clap(long, default_value_t = 0.05)]
+        commission_rate: f64,
+
+        /// memo of a staking validator or a defined asset
+        #[clap(long)]
+        memo: Option<String>,
+
+        /// asset code in base64 to transfer or issue, FRA by default; random to define by default
+        #[clap(long)]
+        asset: Option<String>,
+
+        /// decimals of a defined asset
+        #[clap(long, default_value_t = 6)]
+        decimal: u8,
+
+        /// max units of a defined asset, unlimited by default
+        #[clap(long)]
+        max_supply: Option<u64>,
     },
 }
@@ -1,24 +1,103 @@
 use crate::Network;
 use agent::{
     error::{Error, Result},
-    native::{restore_fra_keypair, restore_xfr_pk_from_str, transfer, NativeOp},
+    native::{
+        claim, define_asset, delegate, issue_asset_to, issue_asset_to_self, parse_asset_code, restore_fra_keypair,
+        restore_xfr_pk_from_str, stake, transfer, transfer_asset, undelegate, NativeOp,
+    },
 };
 use std::path::PathBuf;
+use tracing::info;
 
-pub fn execute_native(
-    network: &Network,
-    op: &NativeOp,
-    secret: &PathBuf,
-    target_addr: &str,
-    amount: u64,
-) -> Result<()> {
+/// The arguments of native operations, each operation takes a part of them
+#[derive(Debug, Clone, Default)]
+pub struct NativeOpts {
+    pub target: Option<String>,
+    pub amount: Option<u64>,
+    /// the tendermint address of a validator
+    pub validator: Option<String>,
+    /// the `priv_validator_key.json` to stake
+    pub validator_key: Option<PathBuf>,
+    pub commission_rate: f64,
+    /// the memo of a staking validator or a defined asset
+    pub memo: Option<String>,
+    /// an asset code in base64, FRA by default
+    pub asset: Option<String>,
+    /// the decimals of a defined asset
+    pub decimal: u8,
+    /// the max units of a defined asset, unlimited by default
+    pub max_supply: Option<u64>,
+}
+
+fn required<'a, T>(value: Option<&'a T>, name: &str, op: &NativeOp) -> Result<&'a T>
+where
+    T: ?Sized,
+{
+    value.ok_or_else(|| Error::Native(format!("--{name} is required by {op:?}")))
+}
+
+pub fn execute_native(network: &Network, op: &NativeOp, secret: &PathBuf, opts: &NativeOpts) -> Result<()> {
     let base = network.base_url();
-    match *op {
+    let kp = restore_fra_keypair(secret)?;
+    let hash = match *op {
         NativeOp::Transfer => {
-            let kp = restore_fra_keypair(secret)?;
-            let target = restore_xfr_pk_from_str(target_addr)?;
-            transfer(base.as_str(), kp, target, amount)
+            let target = restore_xfr_pk_from_str(required(opts.target.as_deref(), "target", op)?)?;
+            let amount = *required(opts.amount.as_ref(), "amount", op)?;
+            return match opts.asset.as_deref() {
+                Some(code) => transfer_asset(base.as_str(), kp, target, amount, parse_asset_code(code)?),
+                None => transfer(base.as_str(), kp, target, amount),
+            };
+        }
+        NativeOp::Delegate => {
+            let validator = required(opts.validator.as_deref(), "validator", op)?;
+            let amount = *required(opts.amount.as_ref(), "amount", op)?;
+            delegate(base.as_str(), kp, validator, amount)
+        }
+        NativeOp::Undelegate => {
+            // a partial undelegation needs both the validator and the amount
+            let partial = match (opts.validator.as_deref(), opts.amount) {
+                (Some(validator), Some(amount)) => Some((validator, amount)),
+                (None, None) => None,
+                _ => {
+                    return Err(Error::Native(
+                        "--validator and --amount are required together by Undelegate".to_string(),
+                    ))
+                }
+            };
+            undelegate(base.as_str(), kp, partial)
+        }
+        NativeOp::Claim => {
+            let validator = required(opts.validator.as_deref(), "validator", op)?;
+            claim(base.as_str(), kp, validator, opts.amount)
+        }
+        NativeOp::Stake => {
+            let validator_key = required(opts.validator_key.as_ref(), "validator-key", op)?;
+            let amount = *required(opts.amount.as_ref(), "amount", op)?;
+            stake(
+                base.as_str(),
+                kp,
+                validator_key,
+                amount,
+                opts.commission_rate,
+                opts.memo.clone(),
+            )
+        }
+        NativeOp::DefineAsset => {
+            let code = opts.asset.as_deref().map(parse_asset_code).transpose()?;
+            let memo = opts.memo.as_deref().unwrap_or_default();
+            let (code, hash) = define_asset(base.as_str(), kp, code, opts.decimal, opts.max_supply, memo)?;
+            info!("asset code {}", code.to_base64());
+            Ok(hash)
+        }
+        NativeOp::IssueAsset => {
+            let code = parse_asset_code(required(opts.asset.as_deref(), "asset", op)?)?;
+            let amount = *required(opts.amount.as_ref(), "amount", op)?;
+            match opts.target.as_deref() {
+                Some(target) => issue_asset_to(base.as_str(), kp, restore_xfr_pk_from_str(target)?, &code, amount),
+                None => issue_asset_to_self(base.as_str(), kp, &code, amount),
+            }
         }
-        _ => Err(Error::Native("Unsupported operation".to_string())),
-    }
+    }?;
+    info!("{:?} sent, tx hash {}", op, hash);
+    Ok(())
 }
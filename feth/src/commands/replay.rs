@@ -0,0 +1,251 @@
+use super::{tests::Recorder, Network};
+use agent::{
+    error::{ErrorCounters, Result},
+    metrics::now_millis,
+    presign::{read_raw_txs, write_raw_txs},
+    tracker::DEFAULT_DROP_BLOCKS,
+    utils::{build_source_keys, calc_pool_size},
+    AsyncTestClient, EndpointPolicy, MetricsWriter, RawTx, ReceiptTracker, TestClient, TestClientOpts, TxFee, TxRecord,
+};
+use rayon::prelude::*;
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+use tracing::{error, info};
+use web3::{
+    futures::{stream, StreamExt},
+    types::{Address, U256},
+};
+
+// how many times a tx is sent again if the node is congested
+const MAX_RETRIES: u64 = 3;
+// the wait before the first retry, doubled by each retry
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Sign `count` transfers per source key with local nonces, and write them to `output`.
+///
+/// Nonces start from the pending nonce of each source key, gas prices are taken at start.
+#[allow(clippy::too_many_arguments)]
+pub fn sign_txs(
+    network: &Network,
+    source_file: &Path,
+    source_count: Option<u64>,
+    count: u64,
+    max_par: u64,
+    timeout: Option<u64>,
+    fees: &[TxFee],
+    target_seed: Option<u64>,
+    output: &Path,
+) -> Result<()> {
+    let opts = TestClientOpts {
+        endpoint_url: Some(network.eth_url()),
+        timeout,
+        ..Default::default()
+    };
+    let client = Arc::new(TestClient::setup_with_opts(opts)?);
+    let chain_id = client.chain_id().unwrap().as_u64();
+    let gas_price = client.gas_price().unwrap();
+    info!("chain_id:     {}", chain_id);
+    info!("gas_price:    {}", gas_price);
+    let fees = fees
+        .iter()
+        .map(|fee| {
+            let mut fee = client.rt.block_on(client.inner.resolve_fee(fee))?;
+            fee.gas_price = fee.gas_price.or(Some(gas_price));
+            Ok(fee)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let target_amount = U256::exp10(16); // 0.01 eth
+    let source_keys = build_source_keys(
+        client.clone(),
+        source_file,
+        false,
+        target_amount,
+        source_count,
+        count,
+        max_par,
+        target_seed,
+    )?;
+    if count == 0 || source_keys.is_empty() {
+        error!("Not enough sufficient source accounts or target accounts, skipped.");
+        return Ok(());
+    }
+
+    info!(
+        "signing {} txs of {} source keys...",
+        count as usize * source_keys.len(),
+        source_keys.len()
+    );
+    let now = std::time::Instant::now();
+    let txs = source_keys
+        .par_iter()
+        .map(|(source, address, targets)| {
+            let start = match client.pending_nonce(*address) {
+                Some(nonce) => nonce,
+                None => {
+                    error!("failed to get the nonce of {:?}, skipped", address);
+                    return vec![];
+                }
+            };
+            let mut txs = Vec::with_capacity(targets.len());
+            for (i, target) in targets.iter().enumerate() {
+                let nonce = start + i;
+                let fee = &fees[nonce.low_u64() as usize % fees.len()];
+                match client.sign_transfer_offline(source, target, chain_id, fee, nonce) {
+                    Ok(tx) => txs.push(tx),
+                    Err(e) => {
+                        // the following txs would be stuck behind a nonce gap
+                        error!("failed to sign tx {} of {:?}: {}", nonce, address, e);
+                        break;
+                    }
+                }
+            }
+            txs
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    let elapsed = now.elapsed().as_secs_f64();
+
+    write_raw_txs(output, &txs)?;
+    info!(
+        "Sign summary: total,{},seconds,{:.3},rate,{:.1},output,{}",
+        txs.len(),
+        elapsed,
+        txs.len() as f64 / elapsed,
+        output.display()
+    );
+    Ok(())
+}
+
+// send signed txs of `source` in order, returns the number of accepted txs
+async fn replay_source(
+    client: &AsyncTestClient,
+    source: Address,
+    txs: &[RawTx],
+    recorder: &Recorder<'_>,
+    errors: &ErrorCounters,
+) -> u64 {
+    let mut succeed = 0;
+    for tx in txs {
+        let mut tries = 0;
+        let res = loop {
+            tries += 1;
+            let send_time = now_millis();
+            let res = client.send_signed(Some(source), tx.raw.clone()).await;
+            match &res {
+                Err(e) if e.is_retryable() && tries <= MAX_RETRIES => {
+                    errors.add(e.kind());
+                    tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(tries as u32 - 1)).await;
+                }
+                _ => break (send_time, res),
+            }
+        };
+        let (send_time, res) = res;
+        let mut record = TxRecord {
+            source,
+            target: tx.target,
+            nonce: Some(tx.nonce),
+            send_time,
+            ..Default::default()
+        };
+        match &res {
+            Ok(hash) => record.hash = Some(*hash),
+            Err(e) => record.error = Some(e.kind().to_string()),
+        }
+        recorder.record(record);
+        match res {
+            Ok(_) => succeed += 1,
+            Err(e) => {
+                // the following txs would be stuck behind a nonce gap
+                errors.add(e.kind());
+                break;
+            }
+        }
+    }
+    succeed
+}
+
+/// Send signed txs of `input` with `send_raw_transaction` only, at most at `max_rate` txs per second.
+///
+/// Txs of a source key are sent in order, at most `max_par` source keys are sent concurrently.
+#[allow(clippy::too_many_arguments)]
+pub fn replay_txs(
+    network: &Network,
+    input: &Path,
+    max_par: u64,
+    timeout: Option<u64>,
+    policy: EndpointPolicy,
+    max_rate: Option<f64>,
+    metrics: &Arc<MetricsWriter>,
+    wait_receipt: bool,
+) -> Result<()> {
+    let txs = read_raw_txs(input)?;
+    let total = txs.len();
+    // group txs by source key, keeping the order of nonces
+    let mut sources = HashMap::new();
+    let mut groups: Vec<(Address, Vec<RawTx>)> = vec![];
+    for tx in txs {
+        let idx = *sources.entry(tx.source).or_insert_with(|| {
+            groups.push((tx.source, vec![]));
+            groups.len() - 1
+        });
+        groups[idx].1.push(tx);
+    }
+    info!("{} signed txs of {} source keys loaded", total, groups.len());
+    if groups.is_empty() {
+        return Ok(());
+    }
+
+    let max_in_flight = calc_pool_size(groups.len(), max_par as usize);
+    info!("source keys in flight {}", max_in_flight);
+
+    let opts = TestClientOpts {
+        endpoint_url: Some(network.eth_url()),
+        timeout,
+        policy,
+        max_rate,
+        ..Default::default()
+    };
+    let client = Arc::new(TestClient::setup_with_opts(opts)?);
+    let start_height = client.block_number().unwrap();
+
+    let errors = ErrorCounters::default();
+    let tracker =
+        wait_receipt.then(|| ReceiptTracker::start(client.inner.clone(), DEFAULT_DROP_BLOCKS, Some(metrics.clone())));
+    let recorder = Recorder {
+        metrics,
+        tracker: tracker.as_ref(),
+    };
+
+    info!("starting replay...");
+    let now = std::time::Instant::now();
+    let sends = groups
+        .iter()
+        .map(|(source, txs)| replay_source(&client.inner, *source, txs, &recorder, &errors));
+    let total_succeed = client.rt.block_on(
+        stream::iter(sends)
+            .buffer_unordered(max_in_flight)
+            .fold(0, |total, succeed| async move { total + succeed }),
+    );
+    let elapsed = now.elapsed().as_secs_f64();
+    let end_height = client.block_number().unwrap();
+
+    info!(
+        "Replay summary: total,{}/{},TPS,{:.3},seconds,{:.3},rate,{:.1},height,{},{}",
+        total_succeed,
+        total,
+        total_succeed as f64 / elapsed,
+        elapsed,
+        client.inner.rate.rate(),
+        start_height,
+        end_height,
+    );
+    info!("Error summary: {}", errors);
+    if let Some(tracker) = tracker {
+        info!("waiting for receipts...");
+        info!("Receipt summary: {}", tracker.finish());
+    }
+    metrics.flush()?;
+    info!("tx metrics saved to {:?}", metrics.path());
+
+    Ok(())
+}
@@ -1,30 +1,25 @@
-use crate::commands::{common::read_mnemonics, Network, TxnsType};
+use crate::commands::{
+    common::read_mnemonics,
+    tests::{resolve_fees, staking_test, EthRounds, TxnsType},
+    Network,
+};
 use agent::{
+    endpoint::EndpointPool,
     error::{Error, Result},
     gen_one_eth_key,
+    hd::{self, HdKeys},
     native::{
         deposit, gen_one_mnemonic_default, restore_keypair_from_mnemonic_default, transfer, withdraw, SecpPair,
         XfrKeyPair, TX_FEE_MIN,
     },
-    one_eth_key, TestClient,
+    utils::target_address,
+    EndpointPolicy, HeadWatcher, MetricsWriter, TestClient, TestClientOpts, TxFee,
 };
 use rayon::prelude::*;
-use std::{
-    ops::Mul,
-    path::PathBuf,
-    str::FromStr,
-    sync::{
-        atomic::{AtomicU64, Ordering::Relaxed},
-        Arc,
-    },
-    time::Duration,
-};
+use std::{ops::Mul, path::PathBuf, str::FromStr, sync::Arc};
 use tokio::{runtime::Runtime, task::yield_now};
 use tracing::{debug, error, info};
-use web3::{
-    transports::Http,
-    types::{Address, U256},
-};
+use web3::types::{Address, U256};
 
 fn calc_pool_size(keys: usize, max_threads: usize) -> usize {
     if keys > max_threads {
@@ -44,18 +39,45 @@ pub fn basic_test(
     source_file: &PathBuf,
     timeout: Option<u64>,
     check_balance: bool,
+    policy: EndpointPolicy,
+    txs_per_account: u64,
+    fees: &[TxFee],
+    max_rate: Option<f64>,
+    metrics: Option<&Arc<MetricsWriter>>,
+    wait_receipt: bool,
+    target_seed: Option<u64>,
+    validators: &[String],
 ) -> Result<()> {
     match *txns_type {
-        TxnsType::Eth => basic_eth_test(network, delay, max_par, count, source_file, timeout, check_balance),
+        TxnsType::Eth => basic_eth_test(
+            network,
+            delay,
+            max_par,
+            count,
+            source_file,
+            timeout,
+            check_balance,
+            policy,
+            txs_per_account,
+            fees,
+            max_rate,
+            metrics.ok_or_else(|| Error::Other("eth tests need a metric file".to_string()))?,
+            wait_receipt,
+            target_seed,
+        ),
         TxnsType::Utxo => basic_utxo_test(network, max_par, count, source_file),
         TxnsType::Prism => basic_prism_test(network, max_par, count, source_file),
+        TxnsType::Staking => staking_test(network, max_par, count, source_file, validators),
         TxnsType::Mixed(_x, _y, _z) => {
             todo!();
         }
     }
 }
 
-fn load_source_kps(runtime: &Runtime, source_file: &PathBuf) -> Result<Vec<XfrKeyPair>> {
+pub(super) fn load_source_kps(runtime: &Runtime, source_file: &PathBuf) -> Result<Vec<XfrKeyPair>> {
+    if let Ok(keys) = HdKeys::read(source_file) {
+        return keys.fra_keys();
+    }
     let kps = runtime
         .block_on(async { read_mnemonics(source_file, vec![]).await })?
         .par_iter()
@@ -65,22 +87,10 @@ fn load_source_kps(runtime: &Runtime, source_file: &PathBuf) -> Result<Vec<XfrKe
     Ok(kps)
 }
 
-fn current_height(runtime: &Runtime, web3_client: &web3::Web3<Http>) -> Result<u64> {
-    runtime
-        .block_on(async { web3_client.eth().block_number().await })
-        .map_err(|o| Error::Native(o.to_string()))
-        .map(|h| h.as_u64())
-}
-
-async fn wait_for_new_block(web3_client: &web3::Web3<Http>, last: u64) -> Result<u64> {
-    loop {
-        let current = web3_client.eth().block_number().await.unwrap().as_u64();
-        if current <= last {
-            yield_now().await;
-        } else {
-            break (Ok(current));
-        }
-    }
+// the height watcher of the eth endpoint of a native network
+pub(super) fn native_heads(network: &Network) -> Result<HeadWatcher> {
+    let pool = EndpointPool::new(&[network.eth_url()], None, EndpointPolicy::default())?;
+    HeadWatcher::start(Arc::new(pool))
 }
 
 fn basic_prism_test(network: &Network, _max_threads: u64, count: u64, source_file: &PathBuf) -> Result<()> {
@@ -116,27 +126,25 @@ fn basic_prism_test(network: &Network, _max_threads: u64, count: u64, source_fil
     });
 
     let base = network.base_url();
-    let http_client = Http::new(network.eth_url().as_str()).unwrap();
-    let web3_client = web3::Web3::new(http_client);
-    let mut last = current_height(&runtime, &web3_client)?;
+    let heads = native_heads(network)?;
+    let mut last = heads.height();
     info!("testing starts at height {} ->> endpoint {}", last, base);
 
     runtime.block_on(async move {
         while let Some(chunk) = rx.recv().await {
             info!("chunk count {}", chunk.len());
-            source_kps
-                .par_iter()
-                .zip(&chunk)
-                .for_each(|(kp, (_, target))| deposit(base.as_str(), kp.clone(), *target, 10 * TX_FEE_MIN).unwrap());
+            source_kps.par_iter().zip(&chunk).for_each(|(kp, (_, target))| {
+                deposit(base.as_str(), kp.clone(), *target, 10 * TX_FEE_MIN, None).unwrap()
+            });
 
-            last = wait_for_new_block(&web3_client, last).await.unwrap();
+            last = heads.wait_for(last + 1).await;
 
             source_kps
                 .par_iter()
                 .zip(chunk)
-                .for_each(|(kp, (eth_kp, _))| withdraw(base.as_str(), eth_kp, kp.get_pk(), TX_FEE_MIN).unwrap());
+                .for_each(|(kp, (eth_kp, _))| withdraw(base.as_str(), eth_kp, kp.get_pk(), TX_FEE_MIN, None).unwrap());
 
-            last = wait_for_new_block(&web3_client, last).await.unwrap();
+            last = heads.wait_for(last + 1).await;
         }
     });
 
@@ -176,9 +184,8 @@ fn basic_utxo_test(network: &Network, _max_threads: u64, count: u64, source_file
     });
 
     let base = network.base_url();
-    let http_client = Http::new(network.eth_url().as_str()).unwrap();
-    let web3_client = web3::Web3::new(http_client);
-    let mut last = current_height(&runtime, &web3_client)?;
+    let heads = native_heads(network)?;
+    let mut last = heads.height();
     info!("testing starts at height {} ->> endpoint {}", last, base);
 
     runtime.block_on(async move {
@@ -188,7 +195,7 @@ fn basic_utxo_test(network: &Network, _max_threads: u64, count: u64, source_file
                 .zip(chunk)
                 .for_each(|(kp, target)| transfer(base.as_str(), kp.clone(), target, TX_FEE_MIN).unwrap());
 
-            last = wait_for_new_block(&web3_client, last).await.unwrap();
+            last = heads.wait_for(last + 1).await;
         }
     });
 
@@ -204,9 +211,16 @@ fn basic_eth_test(
     source_file: &PathBuf,
     timeout: Option<u64>,
     check_balance: bool,
+    policy: EndpointPolicy,
+    txs_per_account: u64,
+    fees: &[TxFee],
+    max_rate: Option<f64>,
+    metrics: &Arc<MetricsWriter>,
+    wait_receipt: bool,
+    target_seed: Option<u64>,
 ) -> Result<()> {
-    let source_keys: Vec<agent::KeyPair> =
-        serde_json::from_str(std::fs::read_to_string(source_file).unwrap().as_str()).unwrap();
+    let txs_per_account = txs_per_account.max(1);
+    let source_keys = hd::read_eth_keys(source_file)?;
     let target_amount = web3::types::U256::exp10(16); // 0.01 eth
 
     let max_pool_size = calc_pool_size(source_keys.len(), max_par as usize);
@@ -216,8 +230,14 @@ fn basic_eth_test(
         .unwrap();
     info!("thread pool size {}", max_pool_size);
 
-    let url = network.eth_url();
-    let client = Arc::new(TestClient::setup(Some(url), timeout));
+    let opts = TestClientOpts {
+        endpoint_url: Some(network.eth_url()),
+        timeout,
+        policy,
+        max_rate,
+        ..Default::default()
+    };
+    let client = Arc::new(TestClient::setup_with_opts(opts)?);
 
     let chain_id = client.chain_id().unwrap().as_u64();
     let gas_price = client.gas_price().unwrap();
@@ -225,6 +245,7 @@ fn basic_eth_test(
     info!("gas_price:    {}", gas_price);
     info!("block_number: {}", client.block_number().unwrap());
     info!("frc20 code:   {:?}", client.frc20_code().unwrap());
+    let fees = resolve_fees(fees, gas_price, None);
 
     info!("preparing test data, it could take several minutes...");
     let source_keys = source_keys
@@ -239,14 +260,9 @@ fn basic_eth_test(
             } else {
                 U256::MAX
             };
-            if balance > target_amount.mul(count) {
-                let target = (0..count)
-                    .map(|_| {
-                        (
-                            Address::from_str(one_eth_key().address.as_str()).unwrap(),
-                            target_amount,
-                        )
-                    })
+            if balance > target_amount.mul(count * txs_per_account) {
+                let target = (0..count * txs_per_account)
+                    .map(|i| (target_address(target_seed, &address, i), target_amount))
                     .collect::<Vec<_>>();
                 debug!("account {:?} added to source pool", address);
                 Some((secret, address, target))
@@ -261,53 +277,17 @@ fn basic_eth_test(
         return Ok(());
     }
 
-    let total_succeed = AtomicU64::new(0);
-    let concurrences = if source_keys.len() > max_pool_size {
-        max_pool_size
-    } else {
-        source_keys.len()
+    let rounds = EthRounds {
+        client: client.clone(),
+        sources: &source_keys,
+        txs_per_account,
+        chain_id,
+        fees: &fees,
+        call: None,
+        concurrency: source_keys.len().min(max_pool_size),
+        metrics,
+        wait_receipt,
     };
-
-    // one-thread per source key
-    info!("starting tests...");
-    let start_height = client.block_number().unwrap();
-    let mut last_height = start_height;
-    let total = source_keys.len() * count as usize;
-    let now = std::time::Instant::now();
-    for r in 0..count {
-        loop {
-            let current = client.block_number().unwrap();
-            if current > last_height {
-                last_height = current;
-                break;
-            } else {
-                std::thread::sleep(Duration::from_secs(1));
-            }
-        }
-        let now = std::time::Instant::now();
-        source_keys.par_iter().for_each(|(source, address, targets)| {
-            let target = targets.get(r as usize).unwrap();
-            if let Some(nonce) = client.pending_nonce(*address) {
-                if client
-                    .distribution_simple(source, target, Some(chain_id), Some(gas_price), Some(nonce))
-                    .is_ok()
-                {
-                    total_succeed.fetch_add(1, Relaxed);
-                }
-            }
-        });
-        let elapsed = now.elapsed().as_secs();
-        info!("round {}/{} time {}", r + 1, count, elapsed);
-        std::thread::sleep(Duration::from_secs(delay));
-    }
-
-    let elapsed = now.elapsed().as_secs();
-    let end_height = client.block_number().unwrap();
-
-    let avg = total as f64 / elapsed as f64;
-    info!(
-        "Test result summary: total,{:?}/{},concurrency,{},TPS,{:.3},seconds,{},height,{},{}",
-        total_succeed, total, concurrences, avg, elapsed, start_height, end_height,
-    );
+    rounds.run(count, delay)?;
     Ok(())
 }
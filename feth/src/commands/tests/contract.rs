@@ -0,0 +1,211 @@
+use crate::commands::{
+    tests::{resolve_fees, ContractCall, EthRounds},
+    Network,
+};
+use agent::{
+    abi,
+    error::{Error, Result},
+    hd,
+    utils::calc_pool_size,
+    BenchContract, EndpointPolicy, MetricsWriter, TestClient, TestClientOpts, TxFee,
+};
+use rayon::prelude::*;
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+use tracing::{debug, error, info};
+use web3::types::{Address, Bytes, CallRequest, U256};
+
+/// The contract and the function called by a contract test
+#[derive(Debug, Clone)]
+pub struct ContractOpts {
+    /// the contract to call, it's deployed from `builtin` or `code` if not given
+    pub address: Option<Address>,
+    /// a contract shipped with the binary, in place of `abi` and `code`
+    pub builtin: Option<BenchContract>,
+    pub abi: Option<PathBuf>,
+    /// the hex bytecode file to deploy the contract from
+    pub code: Option<PathBuf>,
+    /// constructor arguments, a JSON array
+    pub deploy_args: String,
+    /// a function name, or a signature of an overloaded function
+    pub function: String,
+    /// function arguments, a JSON array
+    pub args: String,
+    /// the gas limit of calls, estimated by default
+    pub gas: Option<u64>,
+}
+
+fn parse_json_args(args: &str) -> Result<Vec<serde_json::Value>> {
+    serde_json::from_str(args).map_err(|e| Error::Other(format!("args should be a JSON array: {e}")))
+}
+
+/// Call a contract function repeatedly from all source keys, in rounds paced by new blocks.
+///
+/// The contract is deployed by the first source key if no address is given.
+/// Receipts are always tracked, for the gas used and the reverted calls.
+#[allow(clippy::too_many_arguments)]
+pub fn contract_test(
+    network: &Network,
+    contract: &ContractOpts,
+    delay: u64,
+    max_par: u64,
+    count: u64,
+    source_file: &PathBuf,
+    timeout: Option<u64>,
+    check_balance: bool,
+    policy: EndpointPolicy,
+    txs_per_account: u64,
+    fees: &[TxFee],
+    max_rate: Option<f64>,
+    metrics: &Arc<MetricsWriter>,
+) -> Result<()> {
+    let txs_per_account = txs_per_account.max(1);
+    let source_keys = hd::read_eth_keys(source_file)?;
+
+    let contract_abi = match (contract.builtin, contract.abi.as_ref()) {
+        (Some(builtin), _) => builtin.abi(),
+        (None, Some(path)) => abi::load_abi(path).map_err(|e| Error::Other(format!("{e:#}")))?,
+        (None, None) => {
+            return Err(Error::Other(
+                "an ABI file or a builtin contract is required".to_string(),
+            ))
+        }
+    };
+    let function = abi::find_function(&contract_abi, &contract.function).map_err(|e| Error::Other(format!("{e:#}")))?;
+    let data = abi::encode_args(&function.inputs, &parse_json_args(&contract.args)?)
+        .and_then(|args| Ok(function.encode_input(&args)?))
+        .map_err(|e| Error::Other(format!("{e:#}")))?;
+
+    let max_pool_size = calc_pool_size(source_keys.len(), max_par as usize);
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(max_pool_size)
+        .build_global()
+        .unwrap();
+    info!("thread pool size {}", max_pool_size);
+
+    let opts = TestClientOpts {
+        endpoint_url: Some(network.eth_url()),
+        timeout,
+        policy,
+        max_rate,
+        ..Default::default()
+    };
+    let client = Arc::new(TestClient::setup_with_opts(opts)?);
+
+    let chain_id = client.chain_id().unwrap().as_u64();
+    let gas_price = client.gas_price().unwrap();
+    info!("chain_id:     {}", chain_id);
+    info!("gas_price:    {}", gas_price);
+    info!("block_number: {}", client.block_number().unwrap());
+    let fees = resolve_fees(fees, gas_price, None);
+
+    let source_keys = source_keys
+        .iter()
+        .map(|kp| {
+            (
+                secp256k1::SecretKey::from_str(kp.private.as_str()).unwrap(),
+                Address::from_str(kp.address.as_str()).unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let (first_secret, first_address) = match source_keys.first() {
+        Some(first) => *first,
+        None => {
+            error!("No source accounts, skipped.");
+            return Ok(());
+        }
+    };
+
+    let code = match (contract.builtin, contract.code.as_ref()) {
+        (Some(builtin), _) => Some(builtin.code()),
+        (None, Some(code)) => Some(abi::load_code(code).map_err(|e| Error::Other(format!("{e:#}")))?),
+        (None, None) => None,
+    };
+    let address = match (contract.address, code) {
+        (Some(address), _) => address,
+        (None, Some(code)) => {
+            let deploy_args = parse_json_args(&contract.deploy_args)?;
+            let code = abi::deploy_data(&contract_abi, code, &serde_json::Value::Array(deploy_args))
+                .map_err(|e| Error::Other(format!("{e:#}")))?;
+            let (address, _) = client.deploy_contract(&first_secret, Bytes(code), None, &fees[0])?;
+            info!("contract deployed at {:?}", address);
+            address
+        }
+        (None, None) => {
+            return Err(Error::Other(
+                "a contract address, a code file or a builtin contract is required".to_string(),
+            ))
+        }
+    };
+
+    let gas = match contract.gas {
+        Some(gas) => U256::from(gas),
+        None => {
+            let req = CallRequest {
+                from: Some(first_address),
+                to: Some(address),
+                data: Some(Bytes(data.clone())),
+                ..Default::default()
+            };
+            // with a 20% margin, calls may take more gas as the state changes
+            client.estimate_gas(req)? * 6 / 5
+        }
+    };
+    info!(
+        "calling {} of {:?}, gas limit {}",
+        abi::signature(function),
+        address,
+        gas
+    );
+    let call = ContractCall { data: Bytes(data), gas };
+
+    info!("preparing test data, it could take several minutes...");
+    let max_cost = gas * gas_price * count * txs_per_account;
+    // the same calls in all rounds
+    let targets = vec![(address, U256::zero()); txs_per_account as usize];
+    let source_keys = source_keys
+        .into_par_iter()
+        .filter_map(|(secret, address)| {
+            let balance = if check_balance {
+                client.balance(address, None)
+            } else {
+                U256::MAX
+            };
+            if balance > max_cost {
+                debug!("account {:?} added to source pool", address);
+                Some((secret, address, targets.clone()))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if count == 0 || source_keys.is_empty() {
+        error!("Not enough sufficient source accounts, skipped.");
+        return Ok(());
+    }
+
+    let rounds = EthRounds {
+        client: client.clone(),
+        sources: &source_keys,
+        txs_per_account,
+        chain_id,
+        fees: &fees,
+        call: Some(&call),
+        concurrency: source_keys.len().min(max_pool_size),
+        metrics,
+        wait_receipt: true,
+    };
+    let report = rounds.run(count, delay)?.unwrap_or_default();
+    let succeeded = report.included - report.failed - report.unknown;
+    info!(
+        "Contract summary: address,{:?},function,{},gas_limit,{},gas_used,{},avg_gas_used,{},succeeded,{},reverted,{}",
+        address,
+        function.name,
+        gas,
+        report.gas_used,
+        report.gas_used.checked_div(report.included).unwrap_or_default(),
+        succeeded,
+        report.failed,
+    );
+    Ok(())
+}
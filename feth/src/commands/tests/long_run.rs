@@ -1,21 +1,19 @@
-use crate::Network;
+use crate::{
+    commands::tests::{resolve_fees, EthRounds},
+    Network,
+};
 use agent::{
     error::Result,
     utils::{build_source_keys, display_info},
-    TestClient,
-};
-use rayon::prelude::*;
-use std::{
-    ops::Add,
-    path::Path,
-    sync::{
-        atomic::{AtomicU64, Ordering::Relaxed},
-        Arc,
-    },
-    time::Duration,
+    EndpointPolicy, GasOracle, GasStrategy, MetricsWriter, TestClient, TestClientOpts, TxFee,
 };
+use std::{path::Path, sync::Arc};
 use tracing::{error, info};
-use web3::types::U64;
+
+// how many rounds between two endpoint reports
+const REPORT_ROUNDS: u64 = 10;
+// how many blocks between two gas price refreshes if no gas strategy is given
+const GAS_REFRESH_BLOCKS: u64 = 10;
 
 #[allow(clippy::too_many_arguments)]
 pub fn long_run_test<P>(
@@ -27,6 +25,13 @@ pub fn long_run_test<P>(
     check_balance: &bool,
     source_count: &Option<u64>,
     delay: &u64,
+    policy: EndpointPolicy,
+    txs_per_account: &u64,
+    fees: &[TxFee],
+    max_rate: Option<f64>,
+    metrics: &Arc<MetricsWriter>,
+    wait_receipt: bool,
+    target_seed: Option<u64>,
 ) -> Result<()>
 where
     P: AsRef<Path>,
@@ -35,13 +40,23 @@ where
     let source_file = source;
     let timeout = Some(*timeout);
     let count = *count;
+    let txs_per_account = (*txs_per_account).max(1);
 
     let target_amount = web3::types::U256::exp10(14); // 0.0001 eth
 
-    let url = network.eth_url();
-    let client = Arc::new(TestClient::setup(Some(url), timeout));
+    let opts = TestClientOpts {
+        endpoint_url: Some(network.eth_url()),
+        timeout,
+        policy,
+        max_rate,
+        ..Default::default()
+    };
+    let client = Arc::new(TestClient::setup_with_opts(opts)?);
 
     let (chain_id, gas_price) = display_info(client.clone());
+    // follow fee changes over hours instead of sticking to the price at start
+    let oracle = Arc::new(GasOracle::new(GasStrategy::Refresh(GAS_REFRESH_BLOCKS)));
+    let fees = resolve_fees(fees, gas_price, Some(&oracle));
 
     info!("preparing test data, it could take several minutes...");
     let source_keys = build_source_keys(
@@ -50,45 +65,25 @@ where
         *check_balance,
         target_amount,
         *source_count,
-        count,
+        count * txs_per_account,
         max_par,
-    );
+        target_seed,
+    )?;
     if count == 0 || source_keys.is_empty() {
         error!("Not enough sufficient source accounts or target accounts, skipped.");
         return Ok(());
     }
 
-    let total_succeed = AtomicU64::new(0);
-
-    info!("starting tests...");
-    let start_height = client.block_number().unwrap();
-    let mut last_height = start_height;
-    for round in 0..u64::MAX {
-        let now = std::time::Instant::now();
-        source_keys.par_iter().for_each(|(source, address, targets)| {
-            let target = targets.get((round % count) as usize).unwrap();
-            if let Some(nonce) = client.pending_nonce(*address) {
-                if let Ok(_hash) =
-                    client.distribution_simple(source, target, Some(chain_id), Some(gas_price), Some(nonce))
-                {
-                    total_succeed.fetch_add(1, Relaxed);
-                }
-            }
-        });
-
-        let elapsed = now.elapsed().as_secs();
-        info!("round {} time {}", round, elapsed);
-
-        loop {
-            let current = client.block_number().unwrap();
-            if current >= last_height.add(U64::from(*delay)) {
-                last_height = current;
-                break;
-            } else {
-                std::thread::sleep(Duration::from_millis(1000));
-            }
-        }
-    }
-    // we'll never reach here, just to silence the compiler
-    Ok(())
+    let rounds = EthRounds {
+        client: client.clone(),
+        sources: &source_keys,
+        txs_per_account,
+        chain_id,
+        fees: &fees,
+        call: None,
+        concurrency: max_par as usize,
+        metrics,
+        wait_receipt,
+    };
+    rounds.run_forever(*delay, REPORT_ROUNDS)
 }
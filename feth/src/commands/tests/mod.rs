@@ -1,15 +1,43 @@
 mod basic;
+mod contract;
 mod long_run;
+mod staking;
 
 pub use basic::*;
+pub use contract::*;
 pub use long_run::*;
-use std::fmt::Formatter;
+pub use staking::*;
+
+use agent::{
+    error::{Error, ErrorCounters, ErrorKind, Result},
+    metrics::now_millis,
+    tracker::{TrackerReport, DEFAULT_DROP_BLOCKS},
+    utils::log_nonce_gaps,
+    AsyncTestClient, GasOracle, GasStrategy, MetricsWriter, NonceManager, ReceiptTracker, TestClient, TxFee, TxRecord,
+    TxType,
+};
+use std::{
+    fmt::Formatter,
+    future::Future,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering::Relaxed},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{error, info, warn};
+use web3::{
+    futures::{stream, StreamExt},
+    types::{AccessList, Address, Bytes, U256},
+};
 
 #[derive(Debug)]
 pub enum TxnsType {
     Eth,
     Utxo,
     Prism,
+    Staking,
     Mixed(u64, u64, u64),
 }
 
@@ -19,11 +47,19 @@ impl std::fmt::Display for TxnsType {
             Self::Eth => write!(f, "eth"),
             Self::Utxo => write!(f, "utxo"),
             Self::Prism => write!(f, "prism"),
+            Self::Staking => write!(f, "staking"),
             Self::Mixed(x, y, z) => write!(f, "mixed,{x},{y},{z}"),
         }
     }
 }
 
+impl TxnsType {
+    /// native txs are not recorded by tx metrics
+    pub fn is_native(&self) -> bool {
+        matches!(self, Self::Utxo | Self::Prism | Self::Staking)
+    }
+}
+
 impl std::str::FromStr for TxnsType {
     type Err = String;
 
@@ -32,6 +68,7 @@ impl std::str::FromStr for TxnsType {
             "eth" => Ok(Self::Eth),
             "utxo" => Ok(Self::Utxo),
             "prism" => Ok(Self::Prism),
+            "staking" => Ok(Self::Staking),
             n => {
                 let segs = n.splitn(4, ',').collect::<Vec<&str>>();
                 if segs.len() != 4 || unsafe { segs.get_unchecked(0) != &"mixed" } {
@@ -52,3 +89,403 @@ impl std::str::FromStr for TxnsType {
         }
     }
 }
+
+/// Build the fees of `tx_types`, gas prices are left to the test without a `gas_strategy`
+pub fn build_fees(
+    tx_types: &[TxType],
+    max_fee: Option<u64>,
+    priority_fee: Option<u64>,
+    access_list: Option<&PathBuf>,
+    gas_strategy: Option<GasStrategy>,
+) -> Result<Vec<TxFee>> {
+    let access_list = match access_list {
+        Some(file) => {
+            let data = std::fs::read_to_string(file)?;
+            Some(serde_json::from_str::<AccessList>(&data).map_err(|e| Error::Other(e.to_string()))?)
+        }
+        None => None,
+    };
+    let oracle = gas_strategy.map(|s| Arc::new(GasOracle::new(s)));
+    let mut tx_types = tx_types.to_vec();
+    if tx_types.is_empty() {
+        tx_types.push(TxType::default());
+    }
+    Ok(tx_types
+        .into_iter()
+        .map(|tx_type| TxFee {
+            tx_type,
+            gas_price: None,
+            max_fee_per_gas: max_fee.map(U256::from),
+            max_priority_fee_per_gas: priority_fee.map(U256::from),
+            access_list: access_list.clone(),
+            oracle: oracle.clone(),
+        })
+        .collect())
+}
+
+/// Outcome of a burst of consecutive-nonce txs sent by one source key
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Burst {
+    /// txs accepted by the node
+    pub accepted: u64,
+    /// the tx rejected by the node, and the following ones never sent
+    pub rejected: u64,
+    /// accepted txs of each type, indexed by `TxType::index`
+    pub accepted_by_type: [u64; 3],
+    /// rejected and unsent txs of each type, indexed by `TxType::index`
+    pub rejected_by_type: [u64; 3],
+    /// why the burst stopped, if it did
+    pub error: Option<ErrorKind>,
+}
+
+impl Burst {
+    /// some txs of the burst were accepted, but not all of them
+    pub fn is_partial(&self) -> bool {
+        self.accepted > 0 && self.rejected > 0
+    }
+
+    // `count` txs from the one of `nonce` are rejected or never sent, the type of each follows its nonce
+    fn reject(&mut self, fees: &[TxFee], nonce: u64, count: usize, error: ErrorKind) {
+        self.rejected = count as u64;
+        for k in 0..count as u64 {
+            let fee = &fees[(nonce.wrapping_add(k) % fees.len() as u64) as usize];
+            self.rejected_by_type[fee.tx_type.index()] += 1;
+        }
+        self.error = Some(error);
+    }
+}
+
+/// Accepted and rejected txs of each type over a test
+#[derive(Debug, Default)]
+pub(crate) struct TxTypeCounters {
+    accepted: [AtomicU64; 3],
+    rejected: [AtomicU64; 3],
+}
+
+impl TxTypeCounters {
+    pub fn add(&self, burst: &Burst) {
+        for i in 0..TxType::ALL.len() {
+            self.accepted[i].fetch_add(burst.accepted_by_type[i], Relaxed);
+            self.rejected[i].fetch_add(burst.rejected_by_type[i], Relaxed);
+        }
+    }
+
+    /// log one summary line per tx type in use
+    pub fn log_summary(&self, fees: &[TxFee]) {
+        for tx_type in TxType::ALL {
+            if !fees.iter().any(|f| f.tx_type == tx_type) {
+                continue;
+            }
+            info!(
+                "Tx type summary: type,{},accepted,{},rejected,{}",
+                tx_type,
+                self.accepted[tx_type.index()].load(Relaxed),
+                self.rejected[tx_type.index()].load(Relaxed),
+            );
+        }
+    }
+}
+
+/// Where records of sent txs go
+pub(crate) struct Recorder<'a> {
+    pub metrics: &'a MetricsWriter,
+    /// accepted txs are written once resolved by the tracker
+    pub tracker: Option<&'a ReceiptTracker>,
+}
+
+impl<'a> Recorder<'a> {
+    pub fn record(&self, record: TxRecord) {
+        match self.tracker {
+            Some(tracker) if record.hash.is_some() => tracker.track(record),
+            _ => {
+                if let Err(e) = self.metrics.write(&record) {
+                    warn!("failed to write tx metric {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// A contract call sent to targets in place of a transfer
+#[derive(Debug, Clone)]
+pub(crate) struct ContractCall {
+    /// the encoded function call
+    pub data: Bytes,
+    pub gas: U256,
+}
+
+/// Send txs to `targets` one by one with consecutive nonces, transfers or calls of `call` if any.
+///
+/// The burst stops at the first rejected tx, the following ones would be stuck behind a nonce gap.
+/// Tx types go in turn through `fees` by nonce, a record of each sent tx goes to `recorder`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn send_burst(
+    client: &AsyncTestClient,
+    nonces: &NonceManager,
+    source: (secp256k1::SecretKey, Address),
+    targets: &[(Address, U256)],
+    chain_id: u64,
+    fees: &[TxFee],
+    call: Option<&ContractCall>,
+    recorder: &Recorder,
+) -> Burst {
+    let (secret, address) = source;
+    let mut burst = Burst::default();
+    // the nonce expected next, to guess the types of txs never sent
+    let mut expected = 0;
+    for (i, target) in targets.iter().enumerate() {
+        let nonce = match nonces.next(address).await {
+            Ok(nonce) => nonce,
+            Err(e) => {
+                burst.reject(fees, expected, targets.len() - i, e.kind());
+                break;
+            }
+        };
+        expected = nonce.low_u64().wrapping_add(1);
+        let fee = &fees[nonce.low_u64() as usize % fees.len()];
+        let mut record = TxRecord {
+            source: address,
+            target: target.0,
+            nonce: Some(nonce),
+            send_time: now_millis(),
+            ..Default::default()
+        };
+        let res = match call {
+            Some(call) => {
+                client
+                    .contract_call_simple(&secret, target, &call.data, call.gas, Some(chain_id), fee, Some(nonce))
+                    .await
+            }
+            None => {
+                client
+                    .distribution_simple(&secret, target, Some(chain_id), fee, Some(nonce))
+                    .await
+            }
+        };
+        match &res {
+            Ok(hash) => record.hash = Some(*hash),
+            Err(e) => record.error = Some(e.kind().to_string()),
+        }
+        recorder.record(record);
+        match res {
+            Ok(_hash) => {
+                burst.accepted += 1;
+                burst.accepted_by_type[fee.tx_type.index()] += 1;
+            }
+            Err(e) => {
+                let _ = nonces.on_error(address, nonce, &e).await;
+                burst.reject(fees, nonce.low_u64(), targets.len() - i, e.kind());
+                break;
+            }
+        }
+    }
+    burst
+}
+
+/// Run `bursts` concurrently on the runtime of `client`, at most `max_in_flight` at the same time.
+///
+/// Sends are async, so the bursts in flight are not bound by threads.
+pub(crate) fn send_bursts<I>(client: &TestClient, bursts: I, max_in_flight: usize) -> Vec<Burst>
+where
+    I: IntoIterator,
+    I::Item: Future<Output = Burst>,
+{
+    client.rt.block_on(
+        stream::iter(bursts)
+            .buffer_unordered(max_in_flight.max(1))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// A source key, and the targets of its txs in all rounds
+pub(crate) type Source = (secp256k1::SecretKey, Address, Vec<(Address, U256)>);
+
+/// `fees` with the gas price `gas_price` if they have none, and the gas `oracle` if any and they have none
+pub(crate) fn resolve_fees(fees: &[TxFee], gas_price: U256, oracle: Option<&Arc<GasOracle>>) -> Vec<TxFee> {
+    fees.iter()
+        .cloned()
+        .map(|mut fee| {
+            fee.gas_price = fee.gas_price.or(Some(gas_price));
+            if fee.oracle.is_none() {
+                fee.oracle = oracle.cloned();
+            }
+            fee
+        })
+        .collect()
+}
+
+// outcomes of the bursts of all rounds
+#[derive(Default)]
+struct RoundCounters {
+    succeed: AtomicU64,
+    // bursts only partly accepted
+    partial: AtomicU64,
+    by_type: TxTypeCounters,
+    errors: ErrorCounters,
+}
+
+/// Eth txs sent by all sources in rounds, transfers or calls of `call` if any
+pub(crate) struct EthRounds<'a> {
+    pub client: Arc<TestClient>,
+    pub sources: &'a [Source],
+    /// the txs of a burst, each source sends one burst per round
+    pub txs_per_account: u64,
+    pub chain_id: u64,
+    pub fees: &'a [TxFee],
+    pub call: Option<&'a ContractCall>,
+    /// bursts in flight at the same time
+    pub concurrency: usize,
+    pub metrics: &'a Arc<MetricsWriter>,
+    /// track receipts of accepted txs for the receipt summary
+    pub wait_receipt: bool,
+}
+
+impl EthRounds<'_> {
+    /// Send `count` rounds, each on a new block and `delay` seconds after the previous one.
+    ///
+    /// Returns the receipt report if receipts are tracked.
+    pub fn run(&self, count: u64, delay: u64) -> Result<Option<TrackerReport>> {
+        let tracker = self.tracker();
+        let recorder = Recorder {
+            metrics: self.metrics,
+            tracker: tracker.as_ref(),
+        };
+        let counters = RoundCounters::default();
+        let nonces = self.client.nonce_manager();
+        info!("starting tests...");
+        let heads = self.client.heads()?;
+        info!("pacing rounds by newHeads: {}", heads.is_subscribed());
+        let start_height = heads.height();
+        let mut last_height = start_height;
+        let total = self.sources.len() * (count * self.txs_per_account) as usize;
+        let now = Instant::now();
+        for r in 0..count {
+            last_height = heads.wait_for_blocking(last_height + 1);
+            let now = Instant::now();
+            self.send_round(r, &nonces, &recorder, &counters);
+            info!(
+                "round {}/{} time {} rate {:.1}",
+                r + 1,
+                count,
+                now.elapsed().as_secs(),
+                self.client.inner.rate.rate()
+            );
+            std::thread::sleep(Duration::from_secs(delay));
+        }
+
+        let elapsed = now.elapsed().as_secs();
+        let end_height = heads.height();
+
+        let avg = total as f64 / elapsed as f64;
+        info!(
+            "Test result summary: total,{:?}/{},partial,{:?},concurrency,{},TPS,{:.3},seconds,{},height,{},{}",
+            counters.succeed, total, counters.partial, self.concurrency, avg, elapsed, start_height, end_height,
+        );
+        counters.by_type.log_summary(self.fees);
+        info!("Error summary: {}", counters.errors);
+        let report = tracker.map(|tracker| {
+            info!("waiting for receipts...");
+            tracker.finish()
+        });
+        if let Some(report) = report.as_ref() {
+            info!("Receipt summary: {}", report);
+        }
+        self.metrics.flush()?;
+        info!("tx metrics saved to {:?}", self.metrics.path());
+        self.client.inner.pool.log_report();
+        self.log_nonce_gaps(&nonces);
+        Ok(report)
+    }
+
+    /// Send rounds until the process is killed, `delay` blocks apart, with summaries every `report_rounds`.
+    ///
+    /// The targets of a source are sent to again once all of them are.
+    pub fn run_forever(&self, delay: u64, report_rounds: u64) -> Result<()> {
+        let tracker = self.tracker();
+        let recorder = Recorder {
+            metrics: self.metrics,
+            tracker: tracker.as_ref(),
+        };
+        let counters = RoundCounters::default();
+        let nonces = self.client.nonce_manager();
+        info!("starting tests...");
+        let heads = self.client.heads()?;
+        info!("pacing rounds by newHeads: {}", heads.is_subscribed());
+        let mut last_height = heads.height();
+        for round in 0..u64::MAX {
+            let now = Instant::now();
+            self.send_round(round, &nonces, &recorder, &counters);
+            info!(
+                "round {} time {} succeed {:?} partial {:?} rate {:.1}",
+                round,
+                now.elapsed().as_secs(),
+                counters.succeed,
+                counters.partial,
+                self.client.inner.rate.rate()
+            );
+            if round % report_rounds == report_rounds - 1 {
+                counters.by_type.log_summary(self.fees);
+                info!("Error summary: {}", counters.errors);
+                if let Some(tracker) = tracker.as_ref() {
+                    info!("Receipt summary: {}", tracker.report());
+                }
+                if let Err(e) = self.metrics.flush() {
+                    error!("failed to flush tx metrics {}", e);
+                }
+                self.client.inner.pool.log_report();
+                self.log_nonce_gaps(&nonces);
+            }
+
+            last_height = heads.wait_for_blocking(last_height + delay);
+        }
+        // we'll never reach here, just to silence the compiler
+        Ok(())
+    }
+
+    fn tracker(&self) -> Option<ReceiptTracker> {
+        self.wait_receipt.then(|| {
+            ReceiptTracker::start(
+                self.client.inner.clone(),
+                DEFAULT_DROP_BLOCKS,
+                Some(self.metrics.clone()),
+            )
+        })
+    }
+
+    // one burst of each source, to the targets of `round`
+    fn send_round(&self, round: u64, nonces: &NonceManager, recorder: &Recorder, counters: &RoundCounters) {
+        let txs = self.txs_per_account as usize;
+        let bursts = self.sources.iter().map(|(source, address, targets)| {
+            let start = (round as usize % (targets.len() / txs).max(1)) * txs;
+            let targets = &targets[start..(start + txs).min(targets.len())];
+            send_burst(
+                &self.client.inner,
+                nonces,
+                (*source, *address),
+                targets,
+                self.chain_id,
+                self.fees,
+                self.call,
+                recorder,
+            )
+        });
+        for burst in send_bursts(&self.client, bursts, self.concurrency) {
+            counters.succeed.fetch_add(burst.accepted, Relaxed);
+            counters.by_type.add(&burst);
+            if let Some(kind) = burst.error {
+                counters.errors.add(kind);
+            }
+            if burst.is_partial() {
+                counters.partial.fetch_add(1, Relaxed);
+            }
+        }
+    }
+
+    fn log_nonce_gaps(&self, nonces: &NonceManager) {
+        log_nonce_gaps(
+            self.client.clone(),
+            nonces,
+            self.sources.iter().map(|(_, address, _)| *address),
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use crate::commands::{
+    tests::basic::{load_source_kps, native_heads},
+    Network,
+};
+use agent::{
+    error::{Error, Result},
+    native::{claim, delegate, tm_client, tx_height, undelegate, FRA},
+    utils::calc_pool_size,
+};
+use rayon::prelude::*;
+use std::{
+    fmt::Formatter,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+};
+use tracing::{debug, error, info};
+use web3::futures::{stream, StreamExt};
+
+/// The FRA delegated and undelegated in a staking cycle
+const STAKING_AMOUNT: u64 = FRA;
+// blocks waited for the txs of the last round before checking inclusion
+const INCLUSION_BLOCKS: u64 = 2;
+// tx lookups in flight when checking inclusion
+const LOOKUP_CONCURRENCY: usize = 64;
+
+/// The operations of a staking cycle, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StakingOp {
+    Delegate,
+    Claim,
+    Undelegate,
+}
+
+const CYCLE: [StakingOp; 3] = [StakingOp::Delegate, StakingOp::Claim, StakingOp::Undelegate];
+
+impl std::fmt::Display for StakingOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Delegate => write!(f, "delegate"),
+            Self::Claim => write!(f, "claim"),
+            Self::Undelegate => write!(f, "undelegate"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct OpCounters {
+    sent: AtomicU64,
+    accepted: AtomicU64,
+    included: AtomicU64,
+}
+
+impl std::fmt::Display for OpCounters {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sent = self.sent.load(Relaxed);
+        let accepted = self.accepted.load(Relaxed);
+        let included = self.included.load(Relaxed);
+        write!(
+            f,
+            "sent,{},accepted,{},included,{},success_rate,{:.3},inclusion_rate,{:.3}",
+            sent,
+            accepted,
+            included,
+            accepted as f64 / sent.max(1) as f64,
+            included as f64 / accepted.max(1) as f64,
+        )
+    }
+}
+
+/// Run delegate, claim and undelegate cycles from all source keys, a round per block.
+///
+/// Source keys are spread over `validators`, and move to the next validator every cycle.
+pub fn staking_test(
+    network: &Network,
+    max_par: u64,
+    count: u64,
+    source_file: &PathBuf,
+    validators: &[String],
+) -> Result<()> {
+    if validators.is_empty() {
+        return Err(Error::Native("validators are required by staking tests".to_string()));
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let source_kps = load_source_kps(&runtime, source_file)?;
+    info!("{} source keys for testing", source_kps.len());
+    if count == 0 || source_kps.is_empty() {
+        error!("Not enough sufficient source accounts, skipped.");
+        return Ok(());
+    }
+    let max_pool_size = calc_pool_size(source_kps.len(), max_par as usize);
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(max_pool_size)
+        .build_global()
+        .unwrap();
+
+    let counters = [OpCounters::default(), OpCounters::default(), OpCounters::default()];
+    let mut sent = Vec::new();
+
+    let base = network.base_url();
+    let heads = native_heads(network)?;
+    let start_height = heads.height();
+    let mut last = start_height;
+    info!("testing starts at height {} ->> endpoint {}", last, base);
+    let now = std::time::Instant::now();
+    for r in 0..count {
+        let index = (r % CYCLE.len() as u64) as usize;
+        let op = CYCLE[index];
+        let cycle = (r / CYCLE.len() as u64) as usize;
+        let hashes = source_kps
+            .par_iter()
+            .enumerate()
+            .filter_map(|(i, kp)| {
+                let validator = validators[(i + cycle) % validators.len()].as_str();
+                counters[index].sent.fetch_add(1, Relaxed);
+                let res = match op {
+                    StakingOp::Delegate => delegate(base.as_str(), kp.clone(), validator, STAKING_AMOUNT),
+                    StakingOp::Claim => claim(base.as_str(), kp.clone(), validator, None),
+                    StakingOp::Undelegate => undelegate(base.as_str(), kp.clone(), Some((validator, STAKING_AMOUNT))),
+                };
+                match res {
+                    Ok(hash) => {
+                        counters[index].accepted.fetch_add(1, Relaxed);
+                        Some((index, hash))
+                    }
+                    Err(e) => {
+                        debug!("{} of source {} failed: {:?}", op, i, e);
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+        let accepted = hashes.len();
+        sent.extend(hashes);
+
+        last = heads.wait_for_blocking(last + 1);
+        info!("round {}/{} {} accepted {} height {}", r + 1, count, op, accepted, last);
+    }
+    let elapsed = now.elapsed().as_secs();
+    let end_height = heads.height();
+
+    info!("waiting for inclusion...");
+    heads.wait_for_blocking(end_height + INCLUSION_BLOCKS);
+    let tm_client = tm_client(base.as_str())?;
+    let tm_client = &tm_client;
+    let heights = runtime.block_on(
+        stream::iter(&sent)
+            .map(|(index, hash)| async move { (*index, tx_height(tm_client, hash).await) })
+            .buffer_unordered(LOOKUP_CONCURRENCY)
+            .collect::<Vec<_>>(),
+    );
+    for (index, height) in heights {
+        if height.is_some() {
+            counters[index].included.fetch_add(1, Relaxed);
+        }
+    }
+
+    info!(
+        "Staking summary: validators,{},sources,{},rounds,{},seconds,{},height,{},{}",
+        validators.len(),
+        source_kps.len(),
+        count,
+        elapsed,
+        start_height,
+        end_height
+    );
+    for (op, counters) in CYCLE.iter().zip(&counters) {
+        info!("Staking {}: {}", op, counters);
+    }
+    Ok(())
+}
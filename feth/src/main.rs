@@ -7,30 +7,28 @@ use std::{
     sync::{mpsc, Arc},
 };
 
-use agent::{parse_call_json, parse_deploy_json, parse_query_json, utils::*, TestClient};
+use agent::{
+    keystore, native::AssetSpec, parse_call_json, parse_deploy_json, parse_query_json, utils::*, MetricsWriter,
+    TestClient,
+};
 use commands::*;
 use tracing::info;
 use web3::types::{Address, BlockId, BlockNumber, TransactionId, H256, U256, U64};
 
 fn eth_transaction(network: &str, timeout: Option<u64>, hash: H256) {
-    let network = real_network(network);
-    // use first endpoint to fund accounts
-    let client = TestClient::setup(network[0].clone(), timeout);
+    let client = TestClient::setup(Some(network.to_owned()), timeout).expect("failed to connect to the network");
     let tx = client.transaction(TransactionId::from(hash));
     info!("{:?}", tx);
 }
 
 fn eth_account(network: &str, timeout: Option<u64>, account: Address) {
-    let network = real_network(network);
-    // use first endpoint to fund accounts
-    let client = TestClient::setup(network[0].clone(), timeout);
+    let client = TestClient::setup(Some(network.to_owned()), timeout).expect("failed to connect to the network");
     let balance = client.balance(account, None);
     let nonce = client.nonce(account, None);
     info!("{:?}: {} {:?}", account, balance, nonce);
 }
 fn eth_contract(network: &str, timeout: Option<u64>, optype: &ContractOP, config: &PathBuf) -> anyhow::Result<()> {
-    let network = real_network(network);
-    let client = TestClient::setup(network[0].clone(), timeout);
+    let client = TestClient::setup(Some(network.to_owned()), timeout)?;
     match optype {
         ContractOP::Deploy => {
             let deploy_json = parse_deploy_json(config)?;
@@ -104,7 +102,7 @@ fn para_eth_blocks(client: Arc<TestClient>, start: u64, end: u64) {
 }
 
 fn eth_blocks(network: &str, timeout: Option<u64>, start: Option<u64>, count: u64, follow: bool) {
-    let client = TestClient::setup(Some(network.to_string()), timeout);
+    let client = TestClient::setup(Some(network.to_string()), timeout).expect("failed to connect to the network");
     let start = start.unwrap_or_else(|| client.block_number().unwrap().as_u64());
     if !follow && count == 0 {
         panic!("Need a non-zero block count for a non-follow mode");
@@ -165,8 +163,21 @@ fn main() -> anyhow::Result<()> {
             utxo,
             redeposit,
             seq,
+            policy,
+            metrics_dir,
+            metrics_format,
+            hd,
+            mnemonic,
+            start_index,
+            encrypt,
         }) => {
+            let hd = (*hd || mnemonic.is_some()).then(|| HdOpts {
+                mnemonic: mnemonic.clone(),
+                start: *start_index,
+            });
             if !*utxo {
+                let metrics = MetricsWriter::create(metrics_dir, "target.fund", *metrics_format)
+                    .expect("failed to create metric file");
                 fund_accounts(
                     network.eth_url().as_str(),
                     source.clone(),
@@ -176,10 +187,35 @@ fn main() -> anyhow::Result<()> {
                     *redeposit,
                     *seq,
                     *delay,
+                    *policy,
+                    &metrics,
+                    hd,
+                    *encrypt,
                 );
             } else {
-                fund_utxo_accounts(network.base_url().as_str(), source.clone(), *count, *amount, *load)?;
+                fund_utxo_accounts(
+                    network.base_url().as_str(),
+                    source.clone(),
+                    *count,
+                    *amount,
+                    *load,
+                    hd,
+                    *encrypt,
+                )?;
+            }
+            Ok(())
+        }
+        Some(Commands::Keystore { input, output, decrypt }) => {
+            if output.exists() {
+                anyhow::bail!("{:?} already exists", output);
+            }
+            if *decrypt {
+                keystore::decrypt_file(input, output)?;
+            } else {
+                let password = keystore::new_passphrase(output)?;
+                keystore::encrypt_file(input, output, &password)?;
             }
+            info!("{:?} written", output);
             Ok(())
         }
         Some(Commands::Info {
@@ -204,6 +240,33 @@ fn main() -> anyhow::Result<()> {
             eth_blocks(network.eth_url().as_str(), *timeout, *start, *count, *follow);
             Ok(())
         }
+        Some(Commands::Logs {
+            network,
+            timeout,
+            address,
+            contract,
+            manifest,
+            builtin,
+            abi,
+            from_block,
+            to_block,
+            batch,
+            follow,
+            output,
+        }) => {
+            let opts = LogsOpts {
+                address: *address,
+                contract: contract.clone(),
+                manifest: manifest.clone(),
+                builtin: *builtin,
+                abi: abi.clone(),
+                from_block: *from_block,
+                to_block: *to_block,
+                batch: *batch,
+            };
+            watch_logs(network, *timeout, &opts, *follow, output)?;
+            Ok(())
+        }
         Some(Commands::Etl {
             abcid,
             tendermint,
@@ -238,9 +301,37 @@ fn main() -> anyhow::Result<()> {
             source,
             timeout,
             check_balance,
-            wait_receipt: _need_wait_receipt,
+            wait_receipt,
             fetch_block: _need_fetch_block,
+            policy,
+            txs_per_account,
+            tx_type,
+            max_fee,
+            priority_fee,
+            access_list,
+            gas_strategy,
+            max_rate,
+            metrics_dir,
+            metrics_format,
+            target_seed,
+            validators,
+            contract,
+            builtin,
+            abi,
+            code,
+            deploy_args,
+            function,
+            args,
+            gas,
         }) => {
+            // created by eth tests only, native txs have no tx records
+            let create_metrics = || {
+                MetricsWriter::create(metrics_dir, &format!("target.{mode}"), *metrics_format)
+                    .map(Arc::new)
+                    .expect("failed to create metric file")
+            };
+            let fees = build_fees(tx_type, *max_fee, *priority_fee, access_list.as_ref(), *gas_strategy)
+                .expect("invalid fee options");
             match *mode {
                 TestMode::Long => {
                     long_run_test(
@@ -252,6 +343,13 @@ fn main() -> anyhow::Result<()> {
                         check_balance,
                         source_count,
                         delay_in_blocks,
+                        *policy,
+                        txs_per_account,
+                        &fees,
+                        *max_rate,
+                        &create_metrics(),
+                        *wait_receipt,
+                        *target_seed,
                     )
                     .expect("failed to run long-mode-test");
                 }
@@ -265,21 +363,122 @@ fn main() -> anyhow::Result<()> {
                         source,
                         Some(*timeout),
                         false,
+                        *policy,
+                        *txs_per_account,
+                        &fees,
+                        *max_rate,
+                        (!txns_type.is_native()).then(create_metrics).as_ref(),
+                        *wait_receipt,
+                        *target_seed,
+                        validators,
                     )
                     .expect("failed to run basic-mode-test");
                 }
-                _ => panic!("unsupported test mode"),
+                TestMode::Contract => {
+                    let contract = ContractOpts {
+                        address: *contract,
+                        builtin: *builtin,
+                        abi: abi.clone(),
+                        code: code.clone(),
+                        deploy_args: deploy_args.clone(),
+                        function: function.clone().expect("--function is required in contract mode"),
+                        args: args.clone(),
+                        gas: *gas,
+                    };
+                    contract_test(
+                        network,
+                        &contract,
+                        *delay_in_blocks,
+                        *max_threads,
+                        *count,
+                        source,
+                        Some(*timeout),
+                        *check_balance,
+                        *policy,
+                        *txs_per_account,
+                        &fees,
+                        *max_rate,
+                        &create_metrics(),
+                    )
+                    .expect("failed to run contract-mode-test");
+                }
             }
             Ok(())
         }
+        Some(Commands::Sign {
+            network,
+            source,
+            source_count,
+            count,
+            max_threads,
+            timeout,
+            tx_type,
+            max_fee,
+            priority_fee,
+            access_list,
+            gas_strategy,
+            target_seed,
+            output,
+        }) => {
+            let fees = build_fees(tx_type, *max_fee, *priority_fee, access_list.as_ref(), *gas_strategy)
+                .expect("invalid fee options");
+            sign_txs(
+                network,
+                source,
+                *source_count,
+                *count,
+                *max_threads,
+                Some(*timeout),
+                &fees,
+                *target_seed,
+                output,
+            )
+            .expect("failed to sign txs");
+            Ok(())
+        }
+        Some(Commands::Replay {
+            network,
+            input,
+            max_threads,
+            timeout,
+            policy,
+            max_rate,
+            wait_receipt,
+            metrics_dir,
+            metrics_format,
+        }) => {
+            let metrics = MetricsWriter::create(metrics_dir, "target.replay", *metrics_format)
+                .map(Arc::new)
+                .expect("failed to create metric file");
+            replay_txs(
+                network,
+                input,
+                *max_threads,
+                Some(*timeout),
+                *policy,
+                *max_rate,
+                &metrics,
+                *wait_receipt,
+            )
+            .expect("failed to replay txs");
+            Ok(())
+        }
         Some(Commands::Prism {
             network,
             op,
             secret,
             target,
             amount,
+            asset,
+            decimal,
+            max_supply,
         }) => {
-            execute_prism(network, op, secret, target, amount).expect("failed to execute prism");
+            let asset = asset
+                .as_deref()
+                .map(|code| AssetSpec::parse(code, *decimal, *max_supply))
+                .transpose()
+                .expect("invalid asset code");
+            execute_prism(network, op, secret, target, amount, asset.as_ref()).expect("failed to execute prism");
             Ok(())
         }
         Some(Commands::Native {
@@ -288,8 +487,26 @@ fn main() -> anyhow::Result<()> {
             secret,
             target,
             amount,
+            validator,
+            validator_key,
+            commission_rate,
+            memo,
+            asset,
+            decimal,
+            max_supply,
         }) => {
-            execute_native(network, op, secret, target, *amount).expect("failed execute native operation");
+            let opts = NativeOpts {
+                target: target.clone(),
+                amount: *amount,
+                validator: validator.clone(),
+                validator_key: validator_key.clone(),
+                commission_rate: *commission_rate,
+                memo: memo.clone(),
+                asset: asset.clone(),
+                decimal: *decimal,
+                max_supply: *max_supply,
+            };
+            execute_native(network, op, secret, &opts).expect("failed execute native operation");
             Ok(())
         }
         None => Ok(()),
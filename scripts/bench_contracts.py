@@ -0,0 +1,319 @@
+#!/usr/bin/env python3
+"""
+Assemble the benchmark contracts embedded in `agent`, no solc is needed.
+
+The contracts are written in EVM assembly, with ABIs of their solidity equivalents.
+Run it from the repo root to regenerate `agent/contracts/*.bin` and `agent/contracts/*.abi`.
+The tests of `agent/src/bench.rs` check that the selectors and events of each ABI are in its code.
+"""
+import json
+import os
+
+RC = [0x0000000000000001, 0x0000000000008082, 0x800000000000808A, 0x8000000080008000,
+      0x000000000000808B, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+      0x000000000000008A, 0x0000000000000088, 0x0000000080008009, 0x000000008000000A,
+      0x000000008000808B, 0x800000000000008B, 0x8000000000008089, 0x8000000000008003,
+      0x8000000000008002, 0x8000000000000080, 0x000000000000800A, 0x800000008000000A,
+      0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008]
+ROT = [[0, 36, 3, 41, 18], [1, 44, 10, 45, 2], [62, 6, 43, 15, 61], [28, 55, 25, 21, 56], [27, 20, 39, 8, 14]]
+M = (1 << 64) - 1
+def rol(x, n): return ((x << n) | (x >> (64 - n))) & M if n else x
+def f(A):
+    for rc in RC:
+        C = [A[x][0] ^ A[x][1] ^ A[x][2] ^ A[x][3] ^ A[x][4] for x in range(5)]
+        D = [C[(x - 1) % 5] ^ rol(C[(x + 1) % 5], 1) for x in range(5)]
+        A = [[A[x][y] ^ D[x] for y in range(5)] for x in range(5)]
+        B = [[0] * 5 for _ in range(5)]
+        for x in range(5):
+            for y in range(5):
+                B[y][(2 * x + 3 * y) % 5] = rol(A[x][y], ROT[x][y])
+        A = [[B[x][y] ^ ((~B[(x + 1) % 5][y]) & B[(x + 2) % 5][y]) for y in range(5)] for x in range(5)]
+        A[0][0] ^= rc
+    return A
+def keccak256(data: bytes) -> bytes:
+    rate = 136
+    data = bytearray(data) + b'\x01'
+    while len(data) % rate: data += b'\x00'
+    data[-1] |= 0x80
+    A = [[0] * 5 for _ in range(5)]
+    for off in range(0, len(data), rate):
+        block = data[off:off + rate]
+        for i in range(rate // 8):
+            x, y = i % 5, i // 5
+            A[x][y] ^= int.from_bytes(block[8 * i:8 * i + 8], 'little')
+        A = f(A)
+    out = b''
+    for i in range(4):
+        x, y = i % 5, i // 5
+        out += A[x][y].to_bytes(8, 'little')
+    return out
+
+
+OPS = {
+    'STOP': 0x00, 'ADD': 0x01, 'MUL': 0x02, 'SUB': 0x03, 'DIV': 0x04, 'LT': 0x10, 'GT': 0x11, 'EQ': 0x14,
+    'ISZERO': 0x15, 'AND': 0x16, 'OR': 0x17, 'NOT': 0x19, 'SHR': 0x1c, 'SHA3': 0x20, 'CALLER': 0x33,
+    'CALLVALUE': 0x34, 'CALLDATALOAD': 0x35, 'CALLDATASIZE': 0x36, 'CODESIZE': 0x38, 'CODECOPY': 0x39,
+    'POP': 0x50, 'MLOAD': 0x51, 'MSTORE': 0x52, 'SLOAD': 0x54, 'SSTORE': 0x55, 'JUMP': 0x56, 'JUMPI': 0x57,
+    'JUMPDEST': 0x5b, 'LOG1': 0xa1, 'LOG2': 0xa2, 'LOG3': 0xa3, 'RETURN': 0xf3, 'REVERT': 0xfd,
+}
+for i in range(1, 17):
+    OPS[f'DUP{i}'] = 0x7f + i
+    OPS[f'SWAP{i}'] = 0x8f + i
+
+
+def selector(sig):
+    return '0x' + keccak256(sig.encode())[:4].hex()
+
+
+def topic(sig):
+    return '0x' + keccak256(sig.encode()).hex()
+
+
+def push_size(value):
+    # labels are always PUSH2
+    if isinstance(value, str):
+        return 2
+    return max(1, (value.bit_length() + 7) // 8)
+
+
+def assemble(src, consts=None):
+    """
+    Tokens are opcodes, numbers to push, `@label` to push a label, `$name` to push a constant,
+    and `label:` for a JUMPDEST. Comments start with `;`.
+    """
+    consts = consts or {}
+    prog = []
+    for line in src.splitlines():
+        for tok in line.split(';')[0].split():
+            if tok.endswith(':'):
+                prog.append(('label', tok[:-1]))
+            elif tok.startswith('@'):
+                prog.append(('push', tok[1:]))
+            elif tok.startswith('$'):
+                prog.append(('push', consts[tok[1:]]))
+            elif tok[0].isdigit():
+                prog.append(('push', int(tok, 0)))
+            else:
+                prog.append(('op', tok))
+
+    labels, pc = {}, 0
+    for kind, value in prog:
+        if kind == 'label':
+            labels[value] = pc
+        pc += 1 + push_size(value) if kind == 'push' else 1
+
+    code = bytearray()
+    for kind, value in prog:
+        if kind == 'label':
+            code.append(OPS['JUMPDEST'])
+        elif kind == 'op':
+            code.append(OPS[value])
+        else:
+            size = push_size(value)
+            value = labels[value] if isinstance(value, str) else value
+            code.append(0x5f + size)
+            code += value.to_bytes(size, 'big')
+    return bytes(code)
+
+
+def deployer(runtime, ctor='', consts=None):
+    """The constructor `ctor`, then copying the runtime appended to the code and returning it"""
+    offset = 0
+    while True:
+        init = assemble(ctor + f'\n{len(runtime)} DUP1 {offset} 0 CODECOPY 0 RETURN', consts)
+        if len(init) == offset:
+            return init + runtime
+        offset = len(init)
+
+
+def dispatcher(functions):
+    src = '0 CALLDATALOAD 0xe0 SHR\n'
+    for sig, label in functions:
+        src += f'DUP1 {selector(sig)} EQ @{label} JUMPI\n'
+    return src + 'fail: 0 DUP1 REVERT\n'
+
+
+def func(name, inputs=(), outputs=(), mutability='nonpayable'):
+    return {
+        'type': 'function',
+        'name': name,
+        'inputs': [{'name': n, 'type': t} for n, t in inputs],
+        'outputs': [{'name': n, 'type': t} for n, t in outputs],
+        'stateMutability': mutability,
+    }
+
+
+def event(name, inputs):
+    return {
+        'type': 'event',
+        'name': name,
+        'inputs': [{'name': n, 'type': t, 'indexed': i} for n, t, i in inputs],
+        'anonymous': False,
+    }
+
+
+ADDRESS = '0xffffffffffffffffffffffffffffffffffffffff AND'
+RETURN_TOP = '0 MSTORE 0x20 0 RETURN'
+
+# balances are at keccak(owner, 0), allowances at keccak(owner, spender, 1)
+ERC20_CONSTS = {
+    'TRANSFER': int(topic('Transfer(address,address,uint256)'), 16),
+    'APPROVAL': int(topic('Approval(address,address,uint256)'), 16),
+    'SUPPLY': 10**27,
+}
+ERC20_CTOR = '''
+$SUPPLY 0 SSTORE
+CALLER 0 MSTORE 0 0x20 MSTORE $SUPPLY 0x40 0 SHA3 SSTORE
+$SUPPLY 0 MSTORE CALLER 0 $TRANSFER 0x20 0 LOG3
+'''
+ERC20 = dispatcher([
+    ('totalSupply()', 'total_supply'),
+    ('decimals()', 'decimals'),
+    ('balanceOf(address)', 'balance_of'),
+    ('allowance(address,address)', 'allowance'),
+    ('transfer(address,uint256)', 'transfer'),
+    ('approve(address,uint256)', 'approve'),
+    ('transferFrom(address,address,uint256)', 'transfer_from'),
+    ('mint(address,uint256)', 'mint'),
+]) + f'''
+total_supply: 0 SLOAD {RETURN_TOP}
+decimals: 18 {RETURN_TOP}
+balance_of: 4 CALLDATALOAD {ADDRESS} 0 MSTORE 0 0x20 MSTORE 0x40 0 SHA3 SLOAD {RETURN_TOP}
+allowance:
+    4 CALLDATALOAD {ADDRESS} 0 MSTORE 0x24 CALLDATALOAD {ADDRESS} 0x20 MSTORE 1 0x40 MSTORE
+    0x60 0 SHA3 SLOAD {RETURN_TOP}
+
+; from, to and amount are at 0x80, 0xa0 and 0xc0 of memory
+transfer:
+    CALLER 0x80 MSTORE 4 CALLDATALOAD {ADDRESS} 0xa0 MSTORE 0x24 CALLDATALOAD 0xc0 MSTORE
+    @move JUMP
+
+approve:
+    0x24 CALLDATALOAD
+    CALLER 0 MSTORE 4 CALLDATALOAD {ADDRESS} 0x20 MSTORE 1 0x40 MSTORE 0x60 0 SHA3 SSTORE
+    0x24 CALLDATALOAD 0 MSTORE 4 CALLDATALOAD {ADDRESS} CALLER $APPROVAL 0x20 0 LOG3
+    1 {RETURN_TOP}
+
+transfer_from:
+    4 CALLDATALOAD {ADDRESS} 0x80 MSTORE 0x24 CALLDATALOAD {ADDRESS} 0xa0 MSTORE 0x44 CALLDATALOAD 0xc0 MSTORE
+    0x80 MLOAD 0 MSTORE CALLER 0x20 MSTORE 1 0x40 MSTORE 0x60 0 SHA3    ; [slot]
+    DUP1 SLOAD                                                          ; [slot allowance]
+    DUP1 NOT ISZERO @unlimited JUMPI
+    0xc0 MLOAD DUP1 DUP3 LT @fail JUMPI                                 ; [slot allowance amount]
+    SWAP1 SUB SWAP1 SSTORE
+    @move JUMP
+unlimited: POP POP @move JUMP
+
+mint:
+    0x24 CALLDATALOAD 0 SLOAD DUP2 ADD                                  ; [amount supply]
+    DUP1 0 SLOAD GT @fail JUMPI
+    0 SSTORE
+    4 CALLDATALOAD {ADDRESS} 0 MSTORE 0 0x20 MSTORE 0x40 0 SHA3         ; [amount slot]
+    DUP1 SLOAD DUP3 ADD SWAP1 SSTORE
+    0 MSTORE 4 CALLDATALOAD {ADDRESS} 0 $TRANSFER 0x20 0 LOG3
+    STOP
+
+move:
+    0x80 MLOAD 0 MSTORE 0 0x20 MSTORE 0x40 0 SHA3                       ; [slot]
+    DUP1 SLOAD 0xc0 MLOAD DUP1 DUP3 LT @fail JUMPI                      ; [slot balance amount]
+    SWAP1 SUB SWAP1 SSTORE
+    0xa0 MLOAD 0 MSTORE 0 0x20 MSTORE 0x40 0 SHA3
+    DUP1 SLOAD 0xc0 MLOAD ADD SWAP1 SSTORE
+    0xc0 MLOAD 0 MSTORE 0xa0 MLOAD 0x80 MLOAD $TRANSFER 0x20 0 LOG3
+    1 {RETURN_TOP}
+'''
+ERC20_ABI = [
+    {'type': 'constructor', 'inputs': [], 'stateMutability': 'nonpayable'},
+    func('totalSupply', outputs=[('', 'uint256')], mutability='view'),
+    func('decimals', outputs=[('', 'uint8')], mutability='view'),
+    func('balanceOf', [('account', 'address')], [('', 'uint256')], 'view'),
+    func('allowance', [('owner', 'address'), ('spender', 'address')], [('', 'uint256')], 'view'),
+    func('transfer', [('to', 'address'), ('amount', 'uint256')], [('', 'bool')]),
+    func('approve', [('spender', 'address'), ('amount', 'uint256')], [('', 'bool')]),
+    func('transferFrom', [('from', 'address'), ('to', 'address'), ('amount', 'uint256')], [('', 'bool')]),
+    func('mint', [('to', 'address'), ('amount', 'uint256')]),
+    event('Transfer', [('from', 'address', True), ('to', 'address', True), ('value', 'uint256', False)]),
+    event('Approval', [('owner', 'address', True), ('spender', 'address', True), ('value', 'uint256', False)]),
+]
+
+# the size is at slot 0, the value of a key is at keccak(key)
+STORAGE = dispatcher([
+    ('set(uint256,uint256)', 'set'),
+    ('get(uint256)', 'get'),
+    ('size()', 'size'),
+    ('write(uint256)', 'write'),
+]) + f'''
+set: 0x24 CALLDATALOAD 4 CALLDATALOAD 0 MSTORE 0x20 0 SHA3 SSTORE STOP
+get: 4 CALLDATALOAD 0 MSTORE 0x20 0 SHA3 SLOAD {RETURN_TOP}
+size: 0 SLOAD {RETURN_TOP}
+
+; keys size + 1 ..= size + count are written with their own values
+write:
+    0 SLOAD DUP1 4 CALLDATALOAD ADD                                     ; [start end]
+    DUP1 DUP3 GT @fail JUMPI
+    DUP1 0 SSTORE SWAP1                                                 ; [end key]
+write_loop:
+    DUP2 DUP2 LT ISZERO @write_done JUMPI
+    1 ADD DUP1 DUP1 0 MSTORE 0x20 0 SHA3 SSTORE
+    @write_loop JUMP
+write_done: STOP
+'''
+STORAGE_ABI = [
+    func('set', [('key', 'uint256'), ('value', 'uint256')]),
+    func('get', [('key', 'uint256')], [('', 'uint256')], 'view'),
+    func('size', outputs=[('', 'uint256')], mutability='view'),
+    func('write', [('count', 'uint256')]),
+]
+
+# `run` hashes its argument `rounds` times, and saves the result at slot 0
+COMPUTE = dispatcher([
+    ('run(uint256)', 'run'),
+    ('last()', 'last'),
+]) + f'''
+run:
+    4 CALLDATALOAD DUP1 0                                               ; [rounds hash i]
+run_loop:
+    DUP3 DUP2 LT ISZERO @run_done JUMPI
+    SWAP1 0 MSTORE 0x20 0 SHA3 SWAP1
+    1 ADD @run_loop JUMP
+run_done: POP DUP1 0 SSTORE {RETURN_TOP}
+last: 0 SLOAD {RETURN_TOP}
+'''
+COMPUTE_ABI = [
+    func('run', [('rounds', 'uint256')], [('', 'uint256')]),
+    func('last', outputs=[('', 'uint256')], mutability='view'),
+]
+
+EMITTER_CONSTS = {'PING': int(topic('Ping(address,uint256)'), 16)}
+EMITTER = dispatcher([
+    ('emitEvents(uint256)', 'emit'),
+]) + '''
+emit: 4 CALLDATALOAD 0                                                  ; [count i]
+emit_loop:
+    DUP2 DUP2 LT ISZERO @emit_done JUMPI
+    DUP1 0 MSTORE CALLER $PING 0x20 0 LOG2
+    1 ADD @emit_loop JUMP
+emit_done: STOP
+'''
+EMITTER_ABI = [
+    func('emitEvents', [('count', 'uint256')]),
+    event('Ping', [('sender', 'address', True), ('index', 'uint256', False)]),
+]
+
+CONTRACTS = {
+    'erc20': (deployer(assemble(ERC20, ERC20_CONSTS), ERC20_CTOR, ERC20_CONSTS), ERC20_ABI),
+    'storage': (deployer(assemble(STORAGE)), STORAGE_ABI),
+    'compute': (deployer(assemble(COMPUTE)), COMPUTE_ABI),
+    'emitter': (deployer(assemble(EMITTER, EMITTER_CONSTS)), EMITTER_ABI),
+}
+
+if __name__ == '__main__':
+    out = os.path.join('agent', 'contracts')
+    os.makedirs(out, exist_ok=True)
+    for name, (code, abi) in CONTRACTS.items():
+        with open(os.path.join(out, f'{name}.bin'), 'w') as f:
+            f.write(code.hex() + '\n')
+        with open(os.path.join(out, f'{name}.abi'), 'w') as f:
+            json.dump(abi, f, indent=2)
+            f.write('\n')
+        print(f'{name}: {len(code)} bytes')